@@ -0,0 +1,211 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use socket2::SockRef;
+
+/// Closes `stream` with a TCP reset (RST) instead of an orderly shutdown (FIN).
+///
+/// The peer's next read or write fails with `ConnectionReset`.
+/// Data which was not yet received by the peer may be discarded.
+/// This is done by setting `SO_LINGER` to zero before closing the socket,
+/// so clones of `stream` created by `try_clone()` must be dropped as well.
+///
+/// To reset a connection after a number of bytes,
+/// see [`AbortAfter`] or [`FaultyChannel::reset_after()`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{abort, channel};
+/// use std::io::{ErrorKind, Read};
+///
+/// let (local, mut remote) = channel();
+///
+/// abort(local).unwrap();
+///
+/// let error = remote.read(&mut [0; 1]).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+/// ```
+///
+/// [`AbortAfter`]: struct.AbortAfter.html
+/// [`FaultyChannel::reset_after()`]: struct.FaultyChannel.html#method.reset_after
+pub fn abort(stream: TcpStream) -> io::Result<()> {
+    SockRef::from(&stream).set_linger(Some(Duration::from_secs(0)))?;
+
+    drop(stream);
+
+    Ok(())
+}
+
+/// A stream wrapper which resets the connection after a number of bytes.
+///
+/// Bytes read and written through the wrapper are counted together.
+/// Reads and writes are shortened so that exactly the given number of bytes
+/// is transferred, after which the stream is closed with [`abort()`]
+/// and every further call fails with `ConnectionAborted`.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{channel, AbortAfter};
+/// use std::io::{ErrorKind, Read, Write};
+///
+/// let (local, mut remote) = channel();
+/// let mut local = AbortAfter::new(local, 4);
+///
+/// assert_eq!(local.write(b"Hello").unwrap(), 4);
+/// assert!(local.is_aborted());
+///
+/// let mut buf = Vec::new();
+/// let error = remote.read_to_end(&mut buf).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+/// ```
+///
+/// [`abort()`]: fn.abort.html
+#[derive(Debug)]
+pub struct AbortAfter {
+    stream: Option<TcpStream>,
+    remaining: u64,
+}
+
+impl AbortAfter {
+    /// Wraps `stream`, resetting it after `bytes` were transferred.
+    ///
+    /// If `bytes` is zero, the stream is reset by the first read or write.
+    #[inline]
+    pub fn new(stream: TcpStream, bytes: u64) -> AbortAfter {
+        AbortAfter {
+            stream: Some(stream),
+            remaining: bytes,
+        }
+    }
+
+    /// Whether the connection was reset already.
+    #[inline]
+    pub fn is_aborted(&self) -> bool {
+        self.stream.is_none()
+    }
+
+    /// Returns the wrapped stream, or `None` if it was reset already.
+    #[inline]
+    pub fn get_ref(&self) -> Option<&TcpStream> {
+        self.stream.as_ref()
+    }
+
+    /// Transfers at most the remaining number of bytes using `f`.
+    fn transfer(
+        &mut self,
+        len: usize,
+        f: impl FnOnce(&mut TcpStream, usize) -> io::Result<usize>,
+    ) -> io::Result<usize> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Err(io::ErrorKind::ConnectionAborted.into()),
+        };
+
+        if self.remaining > 0 {
+            let limit = (len as u64).min(self.remaining) as usize;
+            let n = f(stream, limit)?;
+
+            self.remaining -= n as u64;
+
+            if self.remaining > 0 || n == 0 {
+                return Ok(n);
+            }
+
+            self.abort()?;
+            return Ok(n);
+        }
+
+        self.abort()?;
+        Err(io::ErrorKind::ConnectionAborted.into())
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        match self.stream.take() {
+            Some(stream) => abort(stream),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Read for AbortAfter {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.transfer(buf.len(), |stream, n| stream.read(&mut buf[..n]))
+    }
+}
+
+impl Write for AbortAfter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.transfer(buf.len(), |stream, n| stream.write(&buf[..n]))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            Some(stream) => stream.flush(),
+            None => Err(io::ErrorKind::ConnectionAborted.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{channel, read_assert};
+
+    use std::io::ErrorKind;
+
+    #[test]
+    fn abort_write() {
+        let (local, mut remote) = channel();
+
+        abort(local).unwrap();
+
+        // the first writes may succeed before the reset arrives
+        let error = loop {
+            if let Err(e) = remote.write_all(b"reset") {
+                break e;
+            }
+        };
+
+        match error.kind() {
+            ErrorKind::ConnectionReset | ErrorKind::BrokenPipe => {}
+            kind => panic!("unexpected error kind: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn abort_after_read() {
+        let (local, mut remote) = channel();
+        let mut local = AbortAfter::new(local, 6);
+
+        remote.write_all(b"abc").unwrap();
+        read_assert!(local, 3, b"abc");
+        assert!(!local.is_aborted());
+
+        remote.write_all(b"defghi").unwrap();
+        read_assert!(local, 3, b"def");
+        assert!(local.is_aborted());
+
+        assert_eq!(
+            local.read(&mut [0; 1]).unwrap_err().kind(),
+            ErrorKind::ConnectionAborted
+        );
+    }
+
+    #[test]
+    fn abort_after_zero() {
+        let (local, mut remote) = channel();
+        let mut local = AbortAfter::new(local, 0);
+
+        assert_eq!(
+            local.write(b"x").unwrap_err().kind(),
+            ErrorKind::ConnectionAborted
+        );
+        assert!(local.is_aborted());
+
+        let error = remote.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+    }
+}
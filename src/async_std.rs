@@ -0,0 +1,87 @@
+/*!
+Channels returning [async-std] streams.
+
+Requires the `async-std` feature.
+The streams are created on the blocking thread pool,
+so the executor is never blocked while waiting for the background thread.
+
+# Example
+
+```
+use tcp_test::async_std::channel;
+use async_std::io::{ReadExt, WriteExt};
+
+# async_std::task::block_on(async {
+let (mut local, mut remote) = channel().await;
+
+local.write_all(b"Hello, reader").await.unwrap();
+
+let mut buf = [0; 13];
+remote.read_exact(&mut buf).await.unwrap();
+
+assert_eq!(&buf, b"Hello, reader");
+# });
+```
+
+[async-std]: https://async.rs
+*/
+
+use crate::{resolve, Error, DEFAULT_ADDRESS};
+
+use ::async_std::net::TcpStream;
+use ::async_std::task;
+
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// Returns two async-std TCP streams pointing at each other.
+///
+/// This is the asynchronous version of [`channel()`]
+/// and uses the same listener.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created.
+///
+/// [`channel()`]: ../fn.channel.html
+#[inline]
+pub async fn channel() -> (TcpStream, TcpStream) {
+    try_channel()
+        .await
+        .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two async-std TCP streams pointing at each other.
+///
+/// This is the asynchronous version of [`channel_on()`]
+/// and uses the same listener.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created.
+///
+/// [`channel_on()`]: ../fn.channel_on.html
+#[inline]
+pub async fn channel_on(address: impl ToSocketAddrs) -> (TcpStream, TcpStream) {
+    try_channel_on(address)
+        .await
+        .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two async-std TCP streams pointing at each other, or an error.
+#[inline]
+pub async fn try_channel() -> Result<(TcpStream, TcpStream), Error> {
+    pair(*DEFAULT_ADDRESS).await
+}
+
+/// Returns two async-std TCP streams pointing at each other, or an error.
+#[inline]
+pub async fn try_channel_on(address: impl ToSocketAddrs) -> Result<(TcpStream, TcpStream), Error> {
+    pair(resolve(address)?).await
+}
+
+/// Creates a pair of std streams on the blocking thread pool and converts them.
+async fn pair(address: SocketAddr) -> Result<(TcpStream, TcpStream), Error> {
+    let (local, remote) = task::spawn_blocking(move || crate::try_channel_on(address)).await?;
+
+    Ok((local.into(), remote.into()))
+}
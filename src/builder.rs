@@ -0,0 +1,271 @@
+use crate::{pair, resolve, Error, DEFAULT_ADDRESS};
+
+use socket2::{SockRef, Socket, TcpKeepalive};
+
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Socket options applied to one end of a channel.
+///
+/// Options which are not set keep the operating system defaults.
+/// See [`ChannelBuilder`] for an example.
+///
+/// [`ChannelBuilder`]: struct.ChannelBuilder.html
+#[derive(Clone, Debug, Default)]
+pub struct SocketOptions {
+    nodelay: Option<bool>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    read_timeout: Option<Option<Duration>>,
+    write_timeout: Option<Option<Duration>>,
+    ttl: Option<u32>,
+    keepalive: Option<Option<Duration>>,
+    linger: Option<Option<Duration>>,
+}
+
+impl SocketOptions {
+    /// Creates a new set of options with nothing set.
+    #[inline]
+    pub fn new() -> SocketOptions {
+        SocketOptions::default()
+    }
+
+    /// Sets `TCP_NODELAY`.
+    #[inline]
+    pub fn nodelay(mut self, nodelay: bool) -> SocketOptions {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    /// Sets `SO_RCVBUF`.
+    ///
+    /// On the connecting end this is set before connecting,
+    /// so that it is taken into account for the TCP window.
+    #[inline]
+    pub fn recv_buffer_size(mut self, size: usize) -> SocketOptions {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets `SO_SNDBUF`.
+    ///
+    /// On the connecting end this is set before connecting.
+    #[inline]
+    pub fn send_buffer_size(mut self, size: usize) -> SocketOptions {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets the read timeout, `None` meaning reads block indefinitely.
+    #[inline]
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> SocketOptions {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the write timeout, `None` meaning writes block indefinitely.
+    #[inline]
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> SocketOptions {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets `IP_TTL`, or the unicast hop limit for IPv6 sockets.
+    #[inline]
+    pub fn ttl(mut self, ttl: u32) -> SocketOptions {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Enables `SO_KEEPALIVE` with the given idle time, or disables it with `None`.
+    #[inline]
+    pub fn keepalive(mut self, time: Option<Duration>) -> SocketOptions {
+        self.keepalive = Some(time);
+        self
+    }
+
+    /// Sets `SO_LINGER`, `None` disabling it.
+    #[inline]
+    pub fn linger(mut self, linger: Option<Duration>) -> SocketOptions {
+        self.linger = Some(linger);
+        self
+    }
+
+    /// Applies the options to `socket`.
+    pub(crate) fn apply(&self, socket: &Socket, address: SocketAddr) -> Result<(), Error> {
+        let result = (|| {
+            if let Some(nodelay) = self.nodelay {
+                socket.set_nodelay(nodelay)?;
+            }
+
+            if let Some(size) = self.recv_buffer_size {
+                socket.set_recv_buffer_size(size)?;
+            }
+
+            if let Some(size) = self.send_buffer_size {
+                socket.set_send_buffer_size(size)?;
+            }
+
+            if let Some(timeout) = self.read_timeout {
+                socket.set_read_timeout(timeout)?;
+            }
+
+            if let Some(timeout) = self.write_timeout {
+                socket.set_write_timeout(timeout)?;
+            }
+
+            if let Some(ttl) = self.ttl {
+                if address.is_ipv6() {
+                    socket.set_unicast_hops_v6(ttl)?;
+                } else {
+                    socket.set_ttl(ttl)?;
+                }
+            }
+
+            match self.keepalive {
+                Some(Some(time)) => {
+                    socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?
+                }
+                Some(None) => socket.set_keepalive(false)?,
+                None => {}
+            }
+
+            if let Some(linger) = self.linger {
+                socket.set_linger(linger)?;
+            }
+
+            Ok(())
+        })();
+
+        result.map_err(Error::Configure)
+    }
+
+    /// Applies the options to an already connected `stream`.
+    pub(crate) fn apply_stream(
+        &self,
+        stream: &TcpStream,
+        address: SocketAddr,
+    ) -> Result<(), Error> {
+        self.apply(&SockRef::from(stream), address)
+    }
+}
+
+/// Creates a channel with socket options set on either end.
+///
+/// The options of the connecting end are set before connecting,
+/// the options of the accepted end directly after accepting.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{ChannelBuilder, SocketOptions};
+/// use std::time::Duration;
+///
+/// let (local, remote) = ChannelBuilder::new()
+///     .local(SocketOptions::new().nodelay(true))
+///     .remote(SocketOptions::new().read_timeout(Some(Duration::from_secs(1))))
+///     .build()
+///     .unwrap();
+///
+/// assert!(local.nodelay().unwrap());
+/// assert_eq!(remote.read_timeout().unwrap(), Some(Duration::from_secs(1)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ChannelBuilder {
+    local: SocketOptions,
+    remote: SocketOptions,
+}
+
+impl ChannelBuilder {
+    /// Creates a new builder without any socket options.
+    #[inline]
+    pub fn new() -> ChannelBuilder {
+        ChannelBuilder::default()
+    }
+
+    /// Sets the options of the first, connecting stream.
+    #[inline]
+    pub fn local(mut self, options: SocketOptions) -> ChannelBuilder {
+        self.local = options;
+        self
+    }
+
+    /// Sets the options of the second, accepted stream.
+    #[inline]
+    pub fn remote(mut self, options: SocketOptions) -> ChannelBuilder {
+        self.remote = options;
+        self
+    }
+
+    /// Sets the same options on both streams.
+    #[inline]
+    pub fn both(self, options: SocketOptions) -> ChannelBuilder {
+        self.local(options.clone()).remote(options)
+    }
+
+    /// Returns two TCP streams pointing at each other,
+    /// using the same listener as [`channel()`].
+    ///
+    /// [`channel()`]: fn.channel.html
+    #[inline]
+    pub fn build(&self) -> Result<(TcpStream, TcpStream), Error> {
+        pair(*DEFAULT_ADDRESS, &self.local, &self.remote)
+    }
+
+    /// Returns two TCP streams pointing at each other,
+    /// using the same listener as [`channel_on()`].
+    ///
+    /// [`channel_on()`]: fn.channel_on.html
+    #[inline]
+    pub fn build_on(&self, address: impl ToSocketAddrs) -> Result<(TcpStream, TcpStream), Error> {
+        pair(resolve(address)?, &self.local, &self.remote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_local() {
+        let (local, remote) = ChannelBuilder::new()
+            .local(
+                SocketOptions::new()
+                    .nodelay(true)
+                    .ttl(42)
+                    .linger(Some(Duration::from_secs(3)))
+                    .keepalive(Some(Duration::from_secs(60))),
+            )
+            .build()
+            .unwrap();
+
+        assert!(local.nodelay().unwrap());
+        assert_eq!(local.ttl().unwrap(), 42);
+        assert!(!remote.nodelay().unwrap());
+
+        let local = SockRef::from(&local);
+        assert_eq!(local.linger().unwrap(), Some(Duration::from_secs(3)));
+        assert!(local.keepalive().unwrap());
+    }
+
+    #[test]
+    fn options_both() {
+        let timeout = Some(Duration::from_secs(2));
+
+        let (local, remote) = ChannelBuilder::new()
+            .both(
+                SocketOptions::new()
+                    .read_timeout(timeout)
+                    .write_timeout(timeout)
+                    .recv_buffer_size(8192),
+            )
+            .build()
+            .unwrap();
+
+        for stream in &[local, remote] {
+            assert_eq!(stream.read_timeout().unwrap(), timeout);
+            assert_eq!(stream.write_timeout().unwrap(), timeout);
+            assert!(SockRef::from(stream).recv_buffer_size().unwrap() >= 8192);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use crate::peek::Peek;
+use crate::read::{default_timeout, ReadTimeout};
+use crate::{resolve, target, Error, Script, ScriptError};
+
+use std::cmp;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The first pause between connection attempts, doubled after every attempt.
+const FIRST_BACKOFF: Duration = Duration::from_millis(1);
+
+/// The longest pause between connection attempts.
+const MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A client for testing servers which own their `TcpListener`.
+///
+/// Connecting is retried until the server is listening,
+/// so the server can be started right before.
+/// The client implements `Read`, `Write` and [`ReadTimeout`],
+/// so the same assertions as for [`channel()`] can be used,
+/// or a whole [`Script`] can be run.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{read_assert, TestClient};
+/// use std::io::{Read, Write};
+/// use std::net::TcpListener;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+///
+/// // the server under test
+/// std::thread::spawn(move || {
+///     for stream in listener.incoming() {
+///         let mut stream = stream.unwrap();
+///         let mut buf = [0; 4];
+///         stream.read_exact(&mut buf).unwrap();
+///         stream.write_all(b"PONG").unwrap();
+///     }
+/// });
+///
+/// let mut client = TestClient::connect(address);
+///
+/// client.write_all(b"PING").unwrap();
+/// read_assert!(client, b"PONG");
+/// ```
+///
+/// [`ReadTimeout`]: trait.ReadTimeout.html
+/// [`channel()`]: fn.channel.html
+/// [`Script`]: struct.Script.html
+#[derive(Debug)]
+pub struct TestClient {
+    stream: TcpStream,
+}
+
+impl TestClient {
+    /// Connects to `address`, retrying until the [`default_timeout()`] passes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no connection could be established,
+    /// see [`try_connect()`] for a fallible version.
+    ///
+    /// [`default_timeout()`]: fn.default_timeout.html
+    /// [`try_connect()`]: #method.try_connect
+    #[inline]
+    pub fn connect(address: impl ToSocketAddrs) -> TestClient {
+        TestClient::try_connect(address, default_timeout())
+            .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+    }
+
+    /// Connects to `address`, retrying with increasing pauses until `timeout` passes.
+    ///
+    /// With a timeout of `None`, connecting is retried indefinitely.
+    pub fn try_connect(
+        address: impl ToSocketAddrs,
+        timeout: Option<Duration>,
+    ) -> Result<TestClient, Error> {
+        let address = resolve(address)?;
+        let stream = connect_retry(address, timeout, || Ok(()))?;
+
+        Ok(TestClient { stream })
+    }
+
+    /// Runs `script` against the server on the current thread.
+    #[inline]
+    pub fn run(&mut self, script: &Script) -> Result<(), ScriptError> {
+        script.run(self)
+    }
+
+    /// Connects `clients` clients to `address` and runs `script` with all of them concurrently.
+    ///
+    /// Returns the result of every client, in the order they connected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a client cannot connect within the [`default_timeout()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use tcp_test::{MockServer, Script, TestClient};
+    ///
+    /// let server = MockServer::builder()
+    ///     .script(Script::new().send(b"hello"))
+    ///     .start()
+    ///     .unwrap();
+    ///
+    /// let script = Script::new().expect(b"hello").expect_eof();
+    ///
+    /// for result in TestClient::run_many(server.addr(), 10, &script) {
+    ///     result.unwrap();
+    /// }
+    /// ```
+    ///
+    /// [`default_timeout()`]: fn.default_timeout.html
+    #[track_caller]
+    pub fn run_many(
+        address: impl ToSocketAddrs,
+        clients: usize,
+        script: &Script,
+    ) -> Vec<Result<(), ScriptError>> {
+        let address = resolve(address).unwrap_or_else(|e| panic!("tcp-test: {}", e));
+
+        let handles: Vec<_> = (0..clients)
+            .map(|_| script.clone().spawn(TestClient::connect(address)))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.try_join())
+            .collect()
+    }
+
+    /// Returns the underlying TCP stream.
+    #[inline]
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Returns the underlying TCP stream mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut TcpStream {
+        &mut self.stream
+    }
+
+    /// Returns the underlying TCP stream.
+    #[inline]
+    pub fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+}
+
+impl Read for TestClient {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TestClient {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl ReadTimeout for TestClient {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.stream.read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        Some(self.stream.peek(buf))
+    }
+}
+
+impl Peek for TestClient {
+    #[inline]
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.peek(buf)
+    }
+}
+
+/// Connects to `address`, retrying with exponential backoff until `timeout` passes.
+///
+/// Unspecified addresses like `0.0.0.0` are replaced by the loopback address.
+/// `check` is called before every attempt and stops retrying if it fails.
+pub(crate) fn connect_retry<F>(
+    address: SocketAddr,
+    timeout: Option<Duration>,
+    mut check: F,
+) -> Result<TcpStream, Error>
+where
+    F: FnMut() -> Result<(), Error>,
+{
+    let address = target(address);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut backoff = FIRST_BACKOFF;
+
+    loop {
+        check()?;
+
+        let error = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+
+                match TcpStream::connect_timeout(&address, cmp::max(remaining, FIRST_BACKOFF)) {
+                    Ok(stream) => return Ok(stream),
+                    Err(e) => e,
+                }
+            }
+            None => match TcpStream::connect(address) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => e,
+            },
+        };
+
+        if let (Some(deadline), Some(timeout)) = (deadline, timeout) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining == Duration::from_secs(0) {
+                return Err(Error::Connect(io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "{} was not reachable within {:?}, the last attempt failed with: {}",
+                        address, timeout, error
+                    ),
+                )));
+            }
+
+            backoff = cmp::min(backoff, remaining);
+        }
+
+        thread::sleep(backoff);
+        backoff = cmp::min(backoff * 2, MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_assert, unused_address, MockServer};
+
+    use std::net::TcpListener;
+
+    #[test]
+    fn connect_late_listener() {
+        let address = unused_address();
+
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+
+            let listener = TcpListener::bind(address).unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"ready").unwrap();
+        });
+
+        let mut client = TestClient::connect(address);
+        read_assert!(client, b"ready");
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn connect_timeout() {
+        let address = unused_address();
+        let start = Instant::now();
+
+        match TestClient::try_connect(address, Some(Duration::from_millis(100))) {
+            Err(Error::Connect(e)) => {
+                assert_eq!(e.kind(), ErrorKind::TimedOut);
+                assert!(e.to_string().contains("was not reachable within 100ms"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn client_script() {
+        let server = MockServer::builder()
+            .script(Script::new().expect(b"GET").send(b"200"))
+            .start()
+            .unwrap();
+
+        let mut client = TestClient::connect(server.addr());
+
+        client
+            .run(&Script::new().send(b"GET").expect(b"200").expect_eof())
+            .unwrap();
+
+        assert_eq!(server.wait_for_connections(1)[0].received(), b"GET");
+    }
+
+    #[test]
+    fn client_run_many() {
+        let server = MockServer::builder()
+            .script(Script::new().expect(b"a").send(b"b"))
+            .start()
+            .unwrap();
+
+        let results =
+            TestClient::run_many(server.addr(), 5, &Script::new().send(b"a").expect(b"c"));
+
+        assert_eq!(results.len(), 5);
+        assert!(results
+            .iter()
+            .all(|result| result.as_ref().unwrap_err().step() == 2));
+        assert_eq!(server.wait_for_connections(5).len(), 5);
+    }
+}
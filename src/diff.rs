@@ -0,0 +1,222 @@
+use std::fmt::Write;
+
+/// Bytes per row of the hexdump.
+const WIDTH: usize = 8;
+
+/// Rows shown before and after the row with the first difference.
+const CONTEXT: usize = 3;
+
+/// Returns the offset of the first byte which differs between `expected` and `actual`,
+/// or `None` if they are equal.
+pub(crate) fn first_difference(expected: &[u8], actual: &[u8]) -> Option<usize> {
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| expected != actual)
+        .or_else(|| {
+            if expected.len() == actual.len() {
+                None
+            } else {
+                Some(expected.len().min(actual.len()))
+            }
+        })
+}
+
+/// Returns a side-by-side hexdump of `expected` and `actual` around their first difference,
+/// or `None` if they are equal.
+///
+/// Rows containing a difference are marked with `>`,
+/// the first differing byte is pointed at with `^^` on both sides.
+pub(crate) fn hex_diff(expected: &[u8], actual: &[u8]) -> Option<String> {
+    let first = first_difference(expected, actual)?;
+    let rows = expected.len().max(actual.len()).div_ceil(WIDTH);
+    let first_row = first / WIDTH;
+    let start = first_row.saturating_sub(CONTEXT);
+    let end = (first_row + CONTEXT + 1).min(rows);
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "first difference at offset {} ({:#x}), expected {} bytes, received {} bytes",
+        first,
+        first,
+        expected.len(),
+        actual.len()
+    );
+    let _ = writeln!(
+        out,
+        "  {:8}  {:<side$} | received",
+        "offset",
+        "expected",
+        side = WIDTH * 3 + 1 + WIDTH
+    );
+
+    if start > 0 {
+        out.push_str("  ...\n");
+    }
+
+    for row in start..end {
+        let offset = row * WIDTH;
+        let expected_row = slice(expected, offset);
+        let actual_row = slice(actual, offset);
+        let marker = if expected_row == actual_row { ' ' } else { '>' };
+
+        let _ = writeln!(
+            out,
+            "{} {:08x}  {} | {}",
+            marker,
+            offset,
+            side(expected_row),
+            side(actual_row)
+        );
+
+        if row == first_row {
+            let column = first - offset;
+            let caret = format!(
+                "{:hex$}^^{:ascii$}^",
+                "",
+                "",
+                hex = column * 3,
+                ascii = (WIDTH - column) * 3 - 2 + 1 + column
+            );
+            let _ = writeln!(
+                out,
+                "  {:8}  {:<side$} | {}",
+                "",
+                caret,
+                caret,
+                side = WIDTH * 3 + 1 + WIDTH
+            );
+        }
+    }
+
+    if end < rows {
+        out.push_str("  ...\n");
+    }
+
+    Some(out)
+}
+
+/// Returns the row of `bytes` starting at `offset`, which may be empty or short.
+fn slice(bytes: &[u8], offset: usize) -> &[u8] {
+    let start = offset.min(bytes.len());
+    let end = (offset + WIDTH).min(bytes.len());
+
+    &bytes[start..end]
+}
+
+/// Formats one side of a row as hex bytes followed by their ASCII representation.
+fn side(row: &[u8]) -> String {
+    let mut out = String::with_capacity(WIDTH * 4 + 1);
+
+    for i in 0..WIDTH {
+        match row.get(i) {
+            Some(byte) => {
+                let _ = write!(out, "{:02x} ", byte);
+            }
+            None => out.push_str("   "),
+        }
+    }
+
+    out.push(' ');
+
+    for i in 0..WIDTH {
+        out.push(match row.get(i) {
+            Some(byte) if byte.is_ascii_graphic() || *byte == b' ' => *byte as char,
+            Some(_) => '.',
+            None => ' ',
+        });
+    }
+
+    out
+}
+
+/// Returns `bytes` with non-printable bytes escaped, like a byte string literal.
+pub(crate) fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|&byte| std::ascii::escape_default(byte))
+        .map(char::from)
+        .collect()
+}
+
+/// Panics with a hex diff if `actual` differs from `expected`, not public API.
+///
+/// `name` identifies the assertion in the panic message.
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_bytes_eq(name: &str, actual: &[u8], expected: &[u8]) {
+    if let Some(diff) = hex_diff(expected, actual) {
+        panic!("{} buffers are not equal, {}", name, diff.trim_end());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_difference_offsets() {
+        assert_eq!(first_difference(b"abc", b"abc"), None);
+        assert_eq!(first_difference(b"abc", b"abd"), Some(2));
+        assert_eq!(first_difference(b"abc", b"ab"), Some(2));
+        assert_eq!(first_difference(b"", b"a"), Some(0));
+    }
+
+    #[test]
+    fn hex_diff_equal() {
+        assert_eq!(hex_diff(b"Hello", b"Hello"), None);
+    }
+
+    #[test]
+    fn hex_diff_layout() {
+        let diff = hex_diff(b"Hello, server!\r\n", b"Hello, seRver!\r\n").unwrap();
+        let lines: Vec<&str> = diff.lines().collect();
+
+        assert_eq!(
+            lines,
+            [
+                "first difference at offset 9 (0x9), expected 16 bytes, received 16 bytes",
+                "  offset    expected                          | received",
+                "  00000000  48 65 6c 6c 6f 2c 20 73  Hello, s | 48 65 6c 6c 6f 2c 20 73  Hello, s",
+                "> 00000008  65 72 76 65 72 21 0d 0a  erver!.. | 65 52 76 65 72 21 0d 0a  eRver!..",
+                "               ^^                     ^       |    ^^                     ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn hex_diff_context() {
+        let expected = vec![0; 100];
+        let mut actual = expected.clone();
+        actual[50] = 1;
+
+        let diff = hex_diff(&expected, &actual).unwrap();
+        let rows: Vec<&str> = diff.lines().skip(2).collect();
+
+        assert_eq!(rows.first(), Some(&"  ..."));
+        assert_eq!(rows.last(), Some(&"  ..."));
+        assert_eq!(rows.iter().filter(|row| row.starts_with('>')).count(), 1);
+        assert!(rows.iter().any(|row| row.starts_with("> 00000030")));
+    }
+
+    #[test]
+    fn hex_diff_short() {
+        let diff = hex_diff(b"abc", b"ab").unwrap();
+
+        assert!(diff.contains("> 00000000  61 62 63"));
+        assert!(diff.contains("| 61 62                    ab "));
+    }
+
+    #[test]
+    fn escape_text() {
+        assert_eq!(escape(b"250 OK\r\n"), "250 OK\\r\\n");
+        assert_eq!(escape(&[0, 0xff, b'"']), "\\x00\\xff\\\"");
+    }
+
+    #[test]
+    #[should_panic(expected = "test buffers are not equal, first difference at offset 1")]
+    fn assert_bytes_eq_panic() {
+        __assert_bytes_eq("test", b"ab", b"aa");
+    }
+}
@@ -0,0 +1,70 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/// The error type returned by [`try_channel()`] and [`try_channel_on()`].
+///
+/// [`try_channel()`]: fn.try_channel.html
+/// [`try_channel_on()`]: fn.try_channel_on.html
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The address could not be resolved to a socket address.
+    Resolve(io::Error),
+
+    /// The internal `TcpListener` could not be bound.
+    Bind(io::Error),
+
+    /// Connecting to the internal `TcpListener` or another server failed.
+    Connect(io::Error),
+
+    /// Accepting the connection on the internal `TcpListener` failed.
+    Accept(io::Error),
+
+    /// Setting a socket option failed.
+    Configure(io::Error),
+
+    /// The TLS configuration is invalid or the TLS handshake failed.
+    Tls(io::Error),
+
+    /// The server process could not be started or exited before listening.
+    Spawn(io::Error),
+
+    /// A background thread could not be spawned or panicked.
+    Thread(io::Error),
+
+    /// The background thread owning the listener is no longer running.
+    Disconnected,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Resolve(e) => write!(f, "failed to resolve address: {}", e),
+            Error::Bind(e) => write!(f, "failed to bind listener: {}", e),
+            Error::Connect(e) => write!(f, "failed to connect to listener: {}", e),
+            Error::Accept(e) => write!(f, "failed to accept connection: {}", e),
+            Error::Configure(e) => write!(f, "failed to set socket option: {}", e),
+            Error::Tls(e) => write!(f, "failed to establish TLS: {}", e),
+            Error::Spawn(e) => write!(f, "failed to start the server process: {}", e),
+            Error::Thread(e) => write!(f, "failed to run a background thread: {}", e),
+            Error::Disconnected => f.write_str("the background thread is no longer running"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Resolve(e)
+            | Error::Bind(e)
+            | Error::Connect(e)
+            | Error::Accept(e)
+            | Error::Configure(e)
+            | Error::Tls(e)
+            | Error::Spawn(e)
+            | Error::Thread(e) => Some(e),
+            Error::Disconnected => None,
+        }
+    }
+}
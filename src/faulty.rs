@@ -0,0 +1,334 @@
+use crate::rng::Rng;
+use crate::{pair, resolve, Error, SocketOptions, DEFAULT_ADDRESS};
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, Builder};
+use std::time::{Duration, Instant};
+
+/// Creates a channel with a relay between both ends which injects faults.
+///
+/// Instead of pointing directly at each other, both returned streams are connected
+/// to background threads which forward the data in chunks,
+/// applying the configured faults to every chunk.
+/// Chunks are in flight concurrently, so the latency delays the data
+/// without limiting the throughput.
+/// Closing the write half of one stream is forwarded to the other stream.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::FaultyChannel;
+/// use std::io::{ErrorKind, Read, Write};
+/// use std::time::{Duration, Instant};
+///
+/// let (mut local, mut remote) = FaultyChannel::new()
+///     .latency(Duration::from_millis(20))
+///     .chunk_size(4)
+///     .build()
+///     .unwrap();
+///
+/// let start = Instant::now();
+/// local.write_all(b"Hello, reader").unwrap();
+///
+/// let mut buf = [0; 13];
+/// remote.read_exact(&mut buf).unwrap();
+/// assert_eq!(&buf, b"Hello, reader");
+/// assert!(start.elapsed() >= Duration::from_millis(20));
+/// ```
+#[derive(Clone, Debug)]
+pub struct FaultyChannel {
+    latency: Duration,
+    jitter: Duration,
+    bandwidth: Option<u64>,
+    chunk_size: usize,
+    reset_after: Option<u64>,
+    seed: u64,
+}
+
+impl Default for FaultyChannel {
+    fn default() -> FaultyChannel {
+        FaultyChannel {
+            latency: Duration::from_secs(0),
+            jitter: Duration::from_secs(0),
+            bandwidth: None,
+            chunk_size: 8192,
+            reset_after: None,
+            seed: 0,
+        }
+    }
+}
+
+impl FaultyChannel {
+    /// Creates a new relay without any faults.
+    #[inline]
+    pub fn new() -> FaultyChannel {
+        FaultyChannel::default()
+    }
+
+    /// Delays every chunk by `latency`.
+    #[inline]
+    pub fn latency(mut self, latency: Duration) -> FaultyChannel {
+        self.latency = latency;
+        self
+    }
+
+    /// Delays every chunk by a random duration of up to `jitter`,
+    /// in addition to the latency.
+    #[inline]
+    pub fn jitter(mut self, jitter: Duration) -> FaultyChannel {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Limits each direction to `bytes_per_second`.
+    #[inline]
+    pub fn bandwidth(mut self, bytes_per_second: u64) -> FaultyChannel {
+        self.bandwidth = Some(bytes_per_second.max(1));
+        self
+    }
+
+    /// Forwards the data in chunks of at most `size` bytes, 8192 by default.
+    ///
+    /// Every chunk is written separately, so reads on the other end
+    /// return at most `size` bytes unless chunks queue up at the reader.
+    #[inline]
+    pub fn chunk_size(mut self, size: usize) -> FaultyChannel {
+        self.chunk_size = size.max(1);
+        self
+    }
+
+    /// Resets both connections after `bytes` were forwarded in total,
+    /// counting both directions.
+    ///
+    /// Both streams then see `ECONNRESET` instead of an orderly shutdown.
+    #[inline]
+    pub fn reset_after(mut self, bytes: u64) -> FaultyChannel {
+        self.reset_after = Some(bytes);
+        self
+    }
+
+    /// Sets the seed for the random jitter, making it reproducible.
+    #[inline]
+    pub fn seed(mut self, seed: u64) -> FaultyChannel {
+        self.seed = seed;
+        self
+    }
+
+    /// Returns two TCP streams connected through the faulty relay,
+    /// using the same listener as [`channel()`].
+    ///
+    /// [`channel()`]: fn.channel.html
+    #[inline]
+    pub fn build(&self) -> Result<(TcpStream, TcpStream), Error> {
+        self.relay(*DEFAULT_ADDRESS)
+    }
+
+    /// Returns two TCP streams connected through the faulty relay,
+    /// using the same listener as [`channel_on()`].
+    ///
+    /// [`channel_on()`]: fn.channel_on.html
+    #[inline]
+    pub fn build_on(&self, address: impl ToSocketAddrs) -> Result<(TcpStream, TcpStream), Error> {
+        self.relay(resolve(address)?)
+    }
+
+    fn relay(&self, address: SocketAddr) -> Result<(TcpStream, TcpStream), Error> {
+        let options = SocketOptions::new().nodelay(true);
+
+        let (local, local_relay) = pair(address, &SocketOptions::new(), &options)?;
+        let (remote_relay, remote) = pair(address, &options, &SocketOptions::new())?;
+
+        let state = Arc::new(State {
+            remaining: AtomicU64::new(self.reset_after.unwrap_or(u64::MAX)),
+            reset: AtomicBool::new(false),
+            sockets: [
+                local_relay.try_clone().map_err(Error::Configure)?,
+                remote_relay.try_clone().map_err(Error::Configure)?,
+            ],
+        });
+
+        let directions = [
+            (local_relay.try_clone(), remote_relay.try_clone(), 0),
+            (Ok(remote_relay), Ok(local_relay), 1),
+        ];
+
+        for (from, to, direction) in directions {
+            let from = from.map_err(Error::Configure)?;
+            let to = to.map_err(Error::Configure)?;
+
+            let (sender, receiver) = mpsc::channel();
+
+            let relay = Relay {
+                faults: self.clone(),
+                rng: Rng::new(self.seed.wrapping_add(direction)),
+                state: state.clone(),
+            };
+
+            let delivery = Delivery {
+                bandwidth: self.bandwidth,
+                state: state.clone(),
+            };
+
+            Builder::new()
+                .name(String::from("tcp-test relay thread"))
+                .spawn(move || relay.run(from, sender))
+                .map_err(Error::Thread)?;
+
+            Builder::new()
+                .name(String::from("tcp-test relay thread"))
+                .spawn(move || delivery.run(to, receiver))
+                .map_err(Error::Thread)?;
+        }
+
+        Ok((local, remote))
+    }
+}
+
+/// State shared by both relay threads.
+struct State {
+    /// Bytes left until the reset
+    remaining: AtomicU64,
+
+    /// Whether the connections were reset
+    reset: AtomicBool,
+
+    /// Both sockets of the relay
+    sockets: [TcpStream; 2],
+}
+
+impl State {
+    /// Closes both relay sockets with RST.
+    fn reset(&self) {
+        self.reset.store(true, Ordering::SeqCst);
+
+        for socket in &self.sockets {
+            let _ = socket2::SockRef::from(socket).set_linger(Some(Duration::from_secs(0)));
+
+            // wakes up the other relay thread without sending anything
+            let _ = socket.shutdown(Shutdown::Read);
+        }
+    }
+}
+
+/// A chunk of data in flight.
+struct Chunk {
+    data: Vec<u8>,
+
+    /// When the chunk arrives at the other end
+    due: Instant,
+
+    /// Whether to reset the connections after delivering the chunk
+    reset: bool,
+}
+
+/// The reading half of one direction of the relay.
+struct Relay {
+    faults: FaultyChannel,
+    rng: Rng,
+    state: Arc<State>,
+}
+
+impl Relay {
+    /// Reads chunks from `from` and queues them until they are due.
+    ///
+    /// Dropping `queue` at EOF lets the delivery forward the orderly shutdown.
+    fn run(mut self, mut from: TcpStream, queue: Sender<Chunk>) {
+        let mut buf = vec![0; self.faults.chunk_size];
+
+        loop {
+            let n = match from.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) => n,
+                Err(e)
+                    if e.kind() == ErrorKind::ConnectionReset
+                        || e.kind() == ErrorKind::ConnectionAborted =>
+                {
+                    // forward the reset instead of an orderly shutdown
+                    self.state.reset();
+                    return;
+                }
+                Err(_) => return,
+            };
+
+            if self.state.reset.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let (n, reset) = self.take(n as u64);
+            let jitter = self.rng.range(0, self.faults.jitter.as_nanos() as u64);
+
+            let chunk = Chunk {
+                data: buf[..n as usize].to_vec(),
+                due: Instant::now() + self.faults.latency + Duration::from_nanos(jitter),
+                reset,
+            };
+
+            if queue.send(chunk).is_err() || reset {
+                return;
+            }
+        }
+    }
+
+    /// Takes up to `n` bytes from the reset budget,
+    /// returning how many bytes may be forwarded and whether to reset afterwards.
+    fn take(&self, n: u64) -> (u64, bool) {
+        let mut remaining = self.state.remaining.load(Ordering::SeqCst);
+
+        loop {
+            let taken = n.min(remaining);
+
+            match self.state.remaining.compare_exchange(
+                remaining,
+                remaining - taken,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return (taken, remaining - taken == 0),
+                Err(current) => remaining = current,
+            }
+        }
+    }
+}
+
+/// The writing half of one direction of the relay.
+struct Delivery {
+    bandwidth: Option<u64>,
+    state: Arc<State>,
+}
+
+impl Delivery {
+    /// Writes the queued chunks to `to` once they are due.
+    fn run(self, mut to: TcpStream, queue: Receiver<Chunk>) {
+        let mut previous = Instant::now();
+
+        for chunk in queue {
+            // keeps the order, even if the jitter of an earlier chunk was larger
+            let due = chunk.due.max(previous);
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+            previous = due;
+
+            if self.state.reset.load(Ordering::SeqCst) || to.write_all(&chunk.data).is_err() {
+                return;
+            }
+
+            if chunk.reset {
+                self.state.reset();
+                return;
+            }
+
+            if let Some(bandwidth) = self.bandwidth {
+                let nanos = chunk.data.len() as u64 * 1_000_000_000 / bandwidth;
+                thread::sleep(Duration::from_nanos(nanos));
+            }
+        }
+
+        if !self.state.reset.load(Ordering::SeqCst) {
+            // forward the orderly shutdown
+            let _ = to.shutdown(Shutdown::Write);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use crate::rng::Rng;
+use crate::{try_channel, Error};
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+/// How [`Fragmented`] splits reads and writes.
+///
+/// [`Fragmented`]: struct.Fragmented.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fragments {
+    /// At most the given number of bytes per call.
+    Fixed(usize),
+
+    /// A random number of bytes in `min..=max` per call,
+    /// reproducible for the same seed.
+    Random {
+        /// The minimum fragment size
+        min: usize,
+        /// The maximum fragment size
+        max: usize,
+        /// The seed of the random sizes
+        seed: u64,
+    },
+
+    /// A single byte per call.
+    EveryByte,
+}
+
+/// A stream wrapper splitting every read and write into fragments.
+///
+/// Every call to `read()` or `write()` transfers at most the next fragment size,
+/// so code calling them directly sees short reads and short writes
+/// at deterministic boundaries. `read_exact()` and `write_all()` still
+/// transfer everything, only in more calls.
+///
+/// Reads and writes use separate fragment sequences.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{Fragmented, Fragments};
+/// use std::io::{Read, Write};
+///
+/// let (mut local, mut remote) = Fragmented::channel(Fragments::Fixed(3));
+///
+/// local.write_all(b"Hello, reader").unwrap();
+///
+/// let mut buf = [0; 13];
+/// let n = remote.read(&mut buf).unwrap();
+/// assert!(n <= 3);
+///
+/// remote.read_exact(&mut buf[n..]).unwrap();
+/// assert_eq!(&buf, b"Hello, reader");
+/// ```
+#[derive(Debug)]
+pub struct Fragmented<S> {
+    inner: S,
+    fragments: Fragments,
+    read: Rng,
+    write: Rng,
+}
+
+impl Fragmented<TcpStream> {
+    /// Returns two fragmented TCP streams pointing at each other,
+    /// created like in [`channel()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the streams cannot be created,
+    /// see [`try_channel()`] for a fallible version.
+    ///
+    /// [`channel()`]: fn.channel.html
+    /// [`try_channel()`]: #method.try_channel
+    #[inline]
+    pub fn channel(fragments: Fragments) -> (Fragmented<TcpStream>, Fragmented<TcpStream>) {
+        Fragmented::try_channel(fragments).unwrap_or_else(|e| panic!("tcp-test: {}", e))
+    }
+
+    /// Returns two fragmented TCP streams pointing at each other, or an error.
+    pub fn try_channel(
+        fragments: Fragments,
+    ) -> Result<(Fragmented<TcpStream>, Fragmented<TcpStream>), Error> {
+        let (local, remote) = try_channel()?;
+
+        // both ends use different sizes, even with the same seed
+        let mut remote = Fragmented::new(remote, fragments);
+        remote.read.next_u64();
+        remote.write.next_u64();
+
+        Ok((Fragmented::new(local, fragments), remote))
+    }
+}
+
+impl<S> Fragmented<S> {
+    /// Wraps `inner`, splitting its reads and writes according to `fragments`.
+    pub fn new(inner: S, fragments: Fragments) -> Fragmented<S> {
+        let seed = match fragments {
+            Fragments::Random { seed, .. } => seed,
+            _ => 0,
+        };
+
+        Fragmented {
+            inner,
+            fragments,
+            read: Rng::new(seed),
+            write: Rng::new(seed.wrapping_add(1)),
+        }
+    }
+
+    /// Returns a reference to the wrapped stream.
+    #[inline]
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped stream.
+    ///
+    /// Reading or writing through it bypasses the fragmentation.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped stream.
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// Returns the next fragment size, at least 1.
+fn next(fragments: Fragments, rng: &mut Rng) -> usize {
+    let size = match fragments {
+        Fragments::Fixed(size) => size,
+        Fragments::Random { min, max, .. } => rng.range(min as u64, max as u64) as usize,
+        Fragments::EveryByte => 1,
+    };
+
+    size.max(1)
+}
+
+impl<S: Read> Read for Fragmented<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = next(self.fragments, &mut self.read).min(buf.len());
+        self.inner.read(&mut buf[..n])
+    }
+}
+
+impl<S: Write> Write for Fragmented<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = next(self.fragments, &mut self.write).min(buf.len());
+        self.inner.write(&buf[..n])
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_assert;
+
+    /// Records the length of every call.
+    #[derive(Default)]
+    struct Recorder(Vec<usize>);
+
+    impl Read for Recorder {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fixed() {
+        let mut stream = Fragmented::new(Recorder::default(), Fragments::Fixed(4));
+
+        stream.write_all(&[0; 10]).unwrap();
+        assert_eq!(stream.get_ref().0, [4, 4, 2]);
+    }
+
+    #[test]
+    fn every_byte() {
+        let mut stream = Fragmented::new(Recorder::default(), Fragments::EveryByte);
+
+        stream.read_exact(&mut [0; 3]).unwrap();
+        assert_eq!(stream.into_inner().0, [1, 1, 1]);
+    }
+
+    #[test]
+    fn random() {
+        let fragments = Fragments::Random {
+            min: 2,
+            max: 5,
+            seed: 42,
+        };
+
+        let mut first = Fragmented::new(Recorder::default(), fragments);
+        let mut second = Fragmented::new(Recorder::default(), fragments);
+
+        first.write_all(&[0; 100]).unwrap();
+        second.write_all(&[0; 100]).unwrap();
+
+        let sizes = &first.get_ref().0;
+        assert_eq!(sizes, &second.get_ref().0);
+        assert!(sizes[..sizes.len() - 1].iter().all(|n| (2..=5).contains(n)));
+    }
+
+    #[test]
+    fn channel_fragmented() {
+        let (mut local, mut remote) = Fragmented::channel(Fragments::EveryByte);
+
+        local.write_all(b"fr4gm3nt").unwrap();
+        read_assert!(remote, 8, b"fr4gm3nt");
+    }
+}
@@ -9,13 +9,18 @@ Everything can be done using the [`channel()`] function:
 use tcp_test::{channel, read_assert};
 use std::io::{Read, Write};
 
-#[test]
+# fn main() {
+#     first_test();
+#     second_test();
+#     third_test();
+# }
 fn first_test() {
     let sent = b"Hello, reader";
 
     let (mut reader, mut writer) = channel();
 
     writer.write_all(sent).unwrap();
+    drop(writer);
 
     let mut read = Vec::new();
     reader.read_to_end(&mut read).unwrap();
@@ -23,7 +28,6 @@ fn first_test() {
     assert_eq!(read, sent);
 }
 
-#[test]
 fn second_test() {
     let sent = b"Interesting story";
 
@@ -34,7 +38,6 @@ fn second_test() {
     read_assert!(reader, sent.len(), sent);
 }
 
-#[test]
 fn third_test() {
     let sent = b"...";
 
@@ -42,75 +45,324 @@ fn third_test() {
 
     writer.write_all(sent).unwrap();
 
-    read_assert!(reader, sent.len(), sent);
+    read_assert!(reader, sent);
 }
 ```
 
+# Features
+
+- `tokio`: the [`tokio`] module with channels returning Tokio streams.
+- `async-std`: the [`async_std`] module with channels returning async-std streams.
+- `regex`: matching regular expressions with [`assert_read_until()`].
+- `smol`: the [`smol`] module with channels returning smol streams.
+- `tls`: the [`tls`] module with channels returning rustls streams.
+
 [`channel()`]: fn.channel.html
+[`assert_read_until()`]: fn.assert_read_until.html
+[`tokio`]: tokio/index.html
+[`async_std`]: async_std/index.html
+[`smol`]: smol/index.html
+[`tls`]: tls/index.html
 */
 
 //todo: Don't use ToSocketAddrs, create a new trait instead
 
 extern crate lazy_static;
 
+mod abort;
+mod builder;
+mod client;
+mod diff;
+mod error;
+mod faulty;
+mod fragment;
+mod mock;
+mod pattern;
+mod peek;
+mod read;
+mod recorder;
+mod rng;
+mod script;
+mod shutdown;
+mod transport;
+mod wait;
+
+#[cfg(feature = "async-std")]
+pub mod async_std;
+#[cfg(feature = "smol")]
+pub mod smol;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod udp;
+#[cfg(unix)]
+pub mod unix;
+
+pub use abort::{abort, AbortAfter};
+pub use builder::{ChannelBuilder, SocketOptions};
+pub use client::TestClient;
+#[doc(hidden)]
+pub use diff::__assert_bytes_eq;
+pub use error::Error;
+pub use faulty::FaultyChannel;
+pub use fragment::{Fragmented, Fragments};
+pub use mock::{Connection, MockServer, MockServerBuilder, MockStream};
+pub use pattern::{
+    assert_read_for, assert_read_until, assert_read_until_timeout, read_for, read_until,
+    read_until_timeout, Pattern,
+};
+pub use peek::{assert_no_data, Peek};
+#[doc(hidden)]
+pub use read::{__ByRef, __Probe, __ReadAssertBlocking, __ReadAssertTimeout};
+pub use read::{
+    assert_read, assert_read_timeout, default_timeout, set_default_timeout, ReadTimeout,
+};
+pub use recorder::{assert_written, Recorder, Until};
+pub use script::{Script, ScriptError, ScriptHandle};
+#[doc(hidden)]
+pub use shutdown::__assert_eof;
+pub use shutdown::half_close;
+pub use transport::Transport;
+pub use wait::{spawn_listening, try_spawn_listening, try_wait_for_listening, wait_for_listening};
+
 use lazy_static::lazy_static;
+use socket2::{Domain, Protocol, Socket, Type};
 
+use std::collections::HashMap;
+use std::io;
 use std::net::*;
-use std::sync::{mpsc, Arc, Mutex, Once};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
 use std::thread::Builder;
 
 lazy_static! {
-    /// `127.0.0.1:31398`
+    /// `127.0.0.1:0`, letting the operating system choose a free port
     static ref DEFAULT_ADDRESS: SocketAddr =
-        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 31398));
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+
+    /// `[::1]:0`
+    static ref DEFAULT_ADDRESS_V6: SocketAddr =
+        SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 0, 0, 0));
+
+    /// `[::ffff:127.0.0.1]:0`
+    static ref DEFAULT_ADDRESS_DUAL_STACK: SocketAddr =
+        SocketAddr::V6(SocketAddrV6::new(Ipv4Addr::LOCALHOST.to_ipv6_mapped(), 0, 0, 0));
+
+    /// All listeners started so far, keyed by both the requested
+    /// and the actually bound address.
+    static ref LISTENERS: Mutex<HashMap<SocketAddr, Arc<Mutex<Listener>>>> =
+        Mutex::new(HashMap::new());
 }
 
-static mut CHANNEL: Option<Arc<Mutex<(mpsc::Sender<()>, mpsc::Receiver<(TcpStream, TcpStream)>)>>> =
-    None;
-static INIT: Once = Once::new();
+/// Handle to a background thread owning a `TcpListener`.
+struct Listener {
+    /// The address the listener is actually bound to
+    address: SocketAddr,
 
-fn init(address: impl ToSocketAddrs) {
-    INIT.call_once(move || {
-        let address = resolve(address);
+    /// Channel for blocking
+    request: mpsc::Sender<SocketOptions>,
 
-        // channel for blocking
-        let (ex_send, receiver) = mpsc::channel();
+    /// Channel for receiving the streams
+    streams: mpsc::Receiver<Result<(TcpStream, TcpStream), Error>>,
+}
 
-        // channel for sending the streams
-        let (sender, ex_recv) = mpsc::channel();
+/// Returns the listener bound to `address`, starting it if necessary.
+fn init(address: SocketAddr) -> Result<Arc<Mutex<Listener>>, Error> {
+    let mut listeners = LISTENERS.lock().unwrap_or_else(PoisonError::into_inner);
 
-        unsafe {
-            CHANNEL = Some(Arc::new(Mutex::new((ex_send, ex_recv))));
-        };
+    if let Some(listener) = listeners.get(&address) {
+        return Ok(listener.clone());
+    }
+
+    let listener = spawn(address)?;
+    let bound = listener.address;
+
+    let listener = Arc::new(Mutex::new(listener));
+    listeners.insert(address, listener.clone());
+    listeners.insert(bound, listener.clone());
+
+    Ok(listener)
+}
+
+/// Binds a listener to `address` and starts its background thread.
+fn spawn(address: SocketAddr) -> Result<Listener, Error> {
+    // channel for blocking
+    let (request, receiver) = mpsc::channel();
+
+    // channel for sending the streams
+    let (sender, streams) = mpsc::channel();
+
+    let listener = bind(address).map_err(Error::Bind)?;
+
+    // differs from `address` if port 0 was requested
+    let address = listener.local_addr().map_err(Error::Bind)?;
+    let target = target(address);
+
+    Builder::new()
+        .name(format!("tcp-test background thread ({})", address))
+        .spawn(move || {
+            // the loop ends once the `Listener` is dropped
+            while let Ok(options) = receiver.recv() {
+                let result = connect(target, &options).and_then(|local| {
+                    let remote = accept(&listener, &local)?;
+
+                    Ok((local, remote))
+                });
+
+                if sender.send(result).is_err() {
+                    return;
+                }
+            }
+        })
+        .map_err(Error::Thread)?;
+
+    Ok(Listener {
+        address,
+        request,
+        streams,
+    })
+}
+
+/// Binds a listener to `address`.
+///
+/// IPv6 listeners only accept IPv6 connections, unless `address` is unspecified
+/// or an IPv4-mapped address, in which case IPv4 connections are accepted too.
+fn bind(address: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+
+    if let SocketAddr::V6(v6) = address {
+        let dual_stack = v6.ip().is_unspecified() || v6.ip().to_ipv4_mapped().is_some();
+        socket.set_only_v6(!dual_stack)?;
+    }
+
+    // same as `TcpListener::bind()`
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+
+    socket.bind(&address.into())?;
+    socket.listen(128)?;
+
+    Ok(socket.into())
+}
+
+/// Returns the address to connect to for reaching a listener bound to `address`.
+///
+/// IPv4-mapped addresses are connected to using IPv4,
+/// unspecified addresses using the loopback address.
+fn target(address: SocketAddr) -> SocketAddr {
+    let address = canonical(address);
+
+    match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), address.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), address.port())
+        }
+        _ => address,
+    }
+}
+
+/// Converts IPv4-mapped IPv6 addresses to IPv4 addresses.
+fn canonical(address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(ip.into(), v6.port()),
+            None => address,
+        },
+        SocketAddr::V4(_) => address,
+    }
+}
+
+/// Connects to `address`, setting `options` before connecting.
+fn connect(address: SocketAddr, options: &SocketOptions) -> Result<TcpStream, Error> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )
+    .map_err(Error::Connect)?;
+
+    options.apply(&socket, address)?;
+
+    socket.connect(&address.into()).map_err(Error::Connect)?;
+
+    Ok(socket.into())
+}
+
+/// Accepts connections on `listener` until the one coming from `local` arrives.
+///
+/// Stray connections from other sockets are closed.
+fn accept(listener: &TcpListener, local: &TcpStream) -> Result<TcpStream, Error> {
+    let expected = canonical(local.local_addr().map_err(Error::Connect)?);
+
+    loop {
+        let (remote, peer) = listener.accept().map_err(Error::Accept)?;
+
+        if canonical(peer) == expected {
+            return Ok(remote);
+        }
+
+        // dropping closes the stray connection
+        drop(remote);
+    }
+}
+
+/// Removes `listener` after its background thread died,
+/// so that the next call starts a new one.
+fn remove(listener: &Arc<Mutex<Listener>>) {
+    LISTENERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|_, other| !Arc::ptr_eq(other, listener));
+}
+
+/// Returns the address of the listener used by [`channel()`].
+///
+/// The listener is bound to `127.0.0.1` on a port chosen by the operating system,
+/// so test binaries running in parallel never compete for the same port.
+/// It is started if no channel was created yet.
+///
+/// # Panics
+///
+/// Panics if the listener cannot be started.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{channel, listener_addr};
+///
+/// let (local, remote) = channel();
+///
+/// assert_eq!(local.peer_addr().unwrap(), listener_addr());
+/// assert_eq!(remote.local_addr().unwrap(), listener_addr());
+/// ```
+///
+/// [`channel()`]: fn.channel.html
+pub fn listener_addr() -> SocketAddr {
+    let listener = init(*DEFAULT_ADDRESS).unwrap_or_else(|e| panic!("tcp-test: {}", e));
 
-        let listener = TcpListener::bind(address)
-            .expect(concat!("TcpListener::bind() at init(), line ", line!()));
-
-        Builder::new()
-            .name(String::from("tcp-test background thread"))
-            .spawn(move || loop {
-                receiver
-                    .recv()
-                    .expect(concat!("Receiver::recv() at init(), line ", line!()));
-
-                let local = TcpStream::connect(address)
-                    .expect(concat!("TcpStream::connect() at init(), line ", line!()));
-                let remote = listener
-                    .accept()
-                    .expect(concat!("TcpListener::accept() at init(), line ", line!()))
-                    .0;
-
-                sender
-                    .send((local, remote))
-                    .expect(concat!("Sender::send() at init(), line ", line!()));
-            })
-            .expect(concat!("Builder::spawn() at init(), line ", line!()));
-    });
+    let address = listener
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .address;
+
+    address
 }
 
 /// Returns two TCP streams pointing at each other.
 ///
-/// The internal TCP listener is bound to `127.0.0.1:31398`.
+/// The internal TCP listener is bound to `127.0.0.1` on a port
+/// chosen by the operating system, see [`listener_addr()`].
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created,
+/// see [`try_channel()`] for a fallible version.
 ///
 /// # Example
 ///
@@ -118,29 +370,27 @@ fn init(address: impl ToSocketAddrs) {
 /// use tcp_test::channel;
 /// use std::io::{Read, Write};
 ///
-/// #[test]
-/// fn test() {
-///     let data = b"Hello world!";
-///     let (mut local, mut remote) = channel();
+/// let data = b"Hello world!";
+/// let (mut local, mut remote) = channel();
 ///
-///     let local_addr = local.local_addr().unwrap();
-///     let peer_addr = remote.peer_addr().unwrap();
+/// let local_addr = local.local_addr().unwrap();
+/// let peer_addr = remote.peer_addr().unwrap();
 ///
-///     assert_eq!(local_addr, peer_addr);
-///     assert_eq!(local.peer_addr().unwrap(), "127.0.0.1:31398".parse().unwrap()); // default address
+/// assert_eq!(local_addr, peer_addr);
+/// assert_eq!(local.peer_addr().unwrap(), tcp_test::listener_addr()); // default address
 ///
-///     local.write_all(data).unwrap();
+/// local.write_all(data).unwrap();
 ///
-///     let mut buf = [0; 12];
-///     remote.read_exact(&mut buf).unwrap();
+/// let mut buf = [0; 12];
+/// remote.read_exact(&mut buf).unwrap();
 ///
-///     assert_eq!(&buf, data);
-/// }
+/// assert_eq!(&buf, data);
 /// ```
 ///
 /// Also see the [module level example](index.html#example).
 ///
-/// [`listen()`]: fn.listen.html
+/// [`listener_addr()`]: fn.listener_addr.html
+/// [`try_channel()`]: fn.try_channel.html
 #[inline]
 pub fn channel() -> (TcpStream, TcpStream) {
     channel_on(*DEFAULT_ADDRESS)
@@ -149,9 +399,15 @@ pub fn channel() -> (TcpStream, TcpStream) {
 /// Returns two TCP streams pointing at each other.
 ///
 /// The internal TCP listener is bound to `address`.
-/// Only one listener is used throughout the entire program,
-/// so the address should match in all calls to this function,
-/// otherwise it is not specified which address is finally used.
+/// One listener is started per distinct address and shared by
+/// all calls to this function using that address.
+/// If the port is `0`, the operating system chooses a free port once
+/// and all calls using that address share the resulting listener.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created,
+/// see [`try_channel_on()`] for a fallible version.
 ///
 /// # Example
 ///
@@ -159,65 +415,209 @@ pub fn channel() -> (TcpStream, TcpStream) {
 /// use tcp_test::channel_on;
 /// use std::io::{Read, Write};
 ///
-/// #[test]
-/// fn test() {
-///     let data = b"Hello world!";
-///     let (mut local, mut remote) = channel_on("127.0.0.1:31399");
+/// let data = b"Hello world!";
+/// let (mut local, mut remote) = channel_on("127.0.0.1:31399");
 ///
-///     assert_eq!(local.peer_addr().unwrap(), "127.0.0.1:31399".parse().unwrap());
-///     assert_eq!(remote.local_addr().unwrap(), "127.0.0.1:31399".parse().unwrap());
+/// assert_eq!(local.peer_addr().unwrap(), "127.0.0.1:31399".parse().unwrap());
+/// assert_eq!(remote.local_addr().unwrap(), "127.0.0.1:31399".parse().unwrap());
 ///
-///     local.write_all(data).unwrap();
+/// local.write_all(data).unwrap();
 ///
-///     let mut buf = [0; 12];
-///     remote.read_exact(&mut buf).unwrap();
+/// let mut buf = [0; 12];
+/// remote.read_exact(&mut buf).unwrap();
 ///
-///     assert_eq!(&buf, data);
-/// }
+/// assert_eq!(&buf, data);
 /// ```
 ///
-/// [`listen_on()`]: fn.listen_on.html
+/// [`try_channel_on()`]: fn.try_channel_on.html
 #[inline]
 pub fn channel_on(address: impl ToSocketAddrs) -> (TcpStream, TcpStream) {
-    init(address);
+    try_channel_on(address).unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
 
-    let lock = unsafe { CHANNEL.clone().unwrap() };
+/// Returns two TCP streams pointing at each other over IPv6.
+///
+/// The internal TCP listener is bound to `[::1]` on a port
+/// chosen by the operating system.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created, for example if IPv6 is not available,
+/// see [`try_channel_on()`] for a fallible version.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::channel_v6;
+///
+/// let (local, remote) = channel_v6();
+///
+/// assert!(local.local_addr().unwrap().is_ipv6());
+/// assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+/// ```
+///
+/// [`try_channel_on()`]: fn.try_channel_on.html
+#[inline]
+pub fn channel_v6() -> (TcpStream, TcpStream) {
+    channel_on(*DEFAULT_ADDRESS_V6)
+}
 
-    let guard = lock
-        .lock()
-        .expect(concat!("Mutex::lock() at channel_on(), line ", line!()));
+/// Returns two TCP streams pointing at each other,
+/// connecting over IPv4 to a dual-stack IPv6 listener.
+///
+/// The internal TCP listener is bound to `[::ffff:127.0.0.1]`
+/// on a port chosen by the operating system.
+/// The first stream is an IPv4 socket, while the second stream sees
+/// its peer as an IPv4-mapped IPv6 address.
+///
+/// The same behavior can be achieved with [`channel_on()`] by passing
+/// an IPv4-mapped or unspecified IPv6 address.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created,
+/// see [`try_channel_on()`] for a fallible version.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::channel_dual_stack;
+/// use std::net::{IpAddr, Ipv4Addr};
+///
+/// let (local, remote) = channel_dual_stack();
+///
+/// assert!(local.local_addr().unwrap().is_ipv4());
+/// assert!(remote.local_addr().unwrap().is_ipv6());
+///
+/// assert_eq!(
+///     remote.peer_addr().unwrap().ip(),
+///     IpAddr::V6(Ipv4Addr::LOCALHOST.to_ipv6_mapped())
+/// );
+/// ```
+///
+/// [`channel_on()`]: fn.channel_on.html
+/// [`try_channel_on()`]: fn.try_channel_on.html
+#[inline]
+pub fn channel_dual_stack() -> (TcpStream, TcpStream) {
+    channel_on(*DEFAULT_ADDRESS_DUAL_STACK)
+}
+
+/// Returns two TCP streams pointing at each other, or an error.
+///
+/// This is the fallible version of [`channel()`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::try_channel;
+///
+/// let (local, remote) = try_channel().unwrap();
+///
+/// assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+/// ```
+///
+/// [`channel()`]: fn.channel.html
+#[inline]
+pub fn try_channel() -> Result<(TcpStream, TcpStream), Error> {
+    try_channel_on(*DEFAULT_ADDRESS)
+}
 
-    guard
-        .0
-        .send(())
-        .expect(concat!("Sender::send() at channel_on(), line ", line!()));
+/// Returns two TCP streams pointing at each other, or an error.
+///
+/// This is the fallible version of [`channel_on()`].
+/// If the listener for `address` cannot be bound,
+/// the next call with the same address tries again.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{try_channel_on, Error};
+///
+/// let addresses: &[std::net::SocketAddr] = &[];
+///
+/// match try_channel_on(addresses) {
+///     Err(Error::Resolve(_)) => {}
+///     _ => panic!("resolving an empty address list should fail"),
+/// }
+/// ```
+///
+/// [`channel_on()`]: fn.channel_on.html
+#[inline]
+pub fn try_channel_on(address: impl ToSocketAddrs) -> Result<(TcpStream, TcpStream), Error> {
+    let options = SocketOptions::new();
 
-    guard
-        .1
-        .recv()
-        .expect(concat!("Receiver::recv() at channel_on(), line ", line!()))
+    pair(resolve(address)?, &options, &options)
+}
+
+/// Creates a pair of streams using the listener bound to `address`.
+fn pair(
+    address: SocketAddr,
+    local: &SocketOptions,
+    remote: &SocketOptions,
+) -> Result<(TcpStream, TcpStream), Error> {
+    let listener = init(address)?;
+
+    let guard = listener.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let result = guard
+        .request
+        .send(local.clone())
+        .map_err(|_| Error::Disconnected)
+        .and_then(|_| guard.streams.recv().map_err(|_| Error::Disconnected))
+        .and_then(|result| result);
+
+    drop(guard);
+
+    if let Err(Error::Disconnected) = result {
+        remove(&listener);
+    }
+
+    let (local, remote_stream) = result?;
+    remote.apply_stream(&remote_stream, address)?;
+
+    Ok((local, remote_stream))
 }
 
 /// Get the first socket address.
 #[inline]
-fn resolve(address: impl ToSocketAddrs) -> SocketAddr {
+fn resolve(address: impl ToSocketAddrs) -> Result<SocketAddr, Error> {
     address
         .to_socket_addrs()
-        .expect(concat!(
-            "<impl ToSocketAddrs>::to_socket_addrs() at resolve(), line ",
-            line!()
-        ))
+        .map_err(Error::Resolve)?
         .next()
-        .expect(concat!(
-            "ToSocketAddrs::Iter::next() at resolve(), line ",
-            line!()
-        ))
+        .ok_or_else(|| {
+            Error::Resolve(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no socket address was resolved",
+            ))
+        })
+}
+
+/// Returns an address which most likely nobody listens on.
+#[cfg(test)]
+pub(crate) fn unused_address() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
 }
 
 /// Convenience macro for reading and comparing a specific amount of bytes.
 ///
 /// Reads a `$n` number of bytes from `$resource` and then compares that buffer with `$expected`.
-/// Panics if the buffers are not equal.
+/// If `$n` is omitted, the length of `$expected` is used.
+/// `$n` may be any runtime value, the buffer is allocated on the heap.
+/// Panics if the buffers are not equal, showing a hexdump of both around the first difference.
+///
+/// If `$resource` implements [`ReadTimeout`], like `TcpStream` does,
+/// reading fails after the [`default_timeout()`] or the `timeout` given as last argument,
+/// which is either a `Duration` or an `Option<Duration>`.
+/// The panic message then shows which bytes were received so far.
+/// This includes references, `Box` and `BufReader` around such resources.
+/// Every other reader has no timeout and blocks until all bytes are read,
+/// giving them a `timeout` does not compile.
+///
+/// Also see the function form [`assert_read()`].
 ///
 /// # Example
 ///
@@ -238,23 +638,82 @@ fn resolve(address: impl ToSocketAddrs) -> SocketAddr {
 /// }
 ///
 /// read_assert!(Placeholder {}, 3, [1, 2, 3]);
+///
+/// let expected = vec![1, 2, 3];
+/// read_assert!(Placeholder {}, expected.len(), expected);
+/// read_assert!(Placeholder {}, expected);
 /// ```
+///
+/// Using a timeout:
+///
+/// ```should_panic
+/// use tcp_test::{channel, read_assert};
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// local.write_all(b"Hello").unwrap();
+///
+/// // panics: "read_assert! timed out after 100ms, received 5 of 13 bytes: [72, 101, 108, 108, 111]"
+/// read_assert!(remote, b"Hello, reader", timeout = Duration::from_millis(100));
+/// ```
+///
+/// A timeout cannot be applied to resources without [`ReadTimeout`]:
+///
+/// ```compile_fail
+/// use tcp_test::read_assert;
+/// use std::time::Duration;
+///
+/// read_assert!(std::io::empty(), b"", timeout = Duration::from_millis(100));
+/// ```
+///
+/// [`ReadTimeout`]: trait.ReadTimeout.html
+/// [`default_timeout()`]: fn.default_timeout.html
+/// [`assert_read()`]: fn.assert_read.html
 #[macro_export]
 macro_rules! read_assert {
-    ($resource:expr, $n:expr, $expected:expr) => {{
+    ($resource:expr, $expected:expr, timeout = $timeout:expr) => {{
+        match &$expected {
+            expected => {
+                $crate::read_assert!($resource, expected[..].len(), expected, timeout = $timeout);
+            }
+        };
+    }};
+    ($resource:expr, $n:expr, $expected:expr, timeout = $timeout:expr) => {{
         match &$expected {
             expected => {
-                use std::io::Read;
+                use $crate::__ByRef;
 
-                let mut buf = [0; $n];
-                $resource
-                    .read_exact(&mut buf)
-                    .expect("failed to read in read_assert!");
+                let timeout: Option<std::time::Duration> = $timeout.into();
 
-                assert_eq!(
-                    &buf[..],
+                // no fallback, an explicit timeout requires `ReadTimeout`
+                $crate::__ReadAssertTimeout::__read_assert(
+                    &mut $crate::__Probe($resource.__by_ref()),
+                    $n,
                     &expected[..],
-                    "read_assert! buffers are not equal"
+                    timeout,
+                );
+            }
+        };
+    }};
+    ($resource:expr, $expected:expr) => {{
+        match &$expected {
+            expected => {
+                $crate::read_assert!($resource, expected[..].len(), expected);
+            }
+        };
+    }};
+    ($resource:expr, $n:expr, $expected:expr) => {{
+        match &$expected {
+            expected => {
+                #[allow(unused_imports)]
+                use $crate::{__ByRef, __ReadAssertBlocking, __ReadAssertTimeout};
+
+                (&mut $crate::__Probe($resource.__by_ref())).__read_assert(
+                    $n,
+                    &expected[..],
+                    $crate::default_timeout(),
                 );
             }
         };
@@ -267,22 +726,30 @@ mod tests {
 
     #[test]
     fn resolve_ok() {
-        assert_eq!(resolve("127.0.0.1:80"), "127.0.0.1:80".parse().unwrap());
-        assert_eq!(resolve("[::1]:80"), "[::1]:80".parse().unwrap());
+        assert_eq!(
+            resolve("127.0.0.1:80").unwrap(),
+            "127.0.0.1:80".parse().unwrap()
+        );
+        assert_eq!(resolve("[::1]:80").unwrap(), "[::1]:80".parse().unwrap());
 
         let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 80));
         let addrs = [addr; 3];
-        assert_eq!(resolve(addrs.as_ref()), addr);
+        assert_eq!(resolve(addrs.as_ref()).unwrap(), addr);
     }
 
     #[test]
-    #[should_panic]
     fn resolve_err() {
         let addrs: [SocketAddr; 0] = [];
-        resolve(addrs.as_ref());
+
+        match resolve(addrs.as_ref()) {
+            Err(Error::Resolve(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
     }
 
     use std::io::{self, Read};
+    use std::thread;
+    use std::time::Duration;
 
     struct Placeholder;
 
@@ -303,6 +770,119 @@ mod tests {
         read_assert!(Placeholder {}, 1, [0xff]);
     }
 
+    #[test]
+    fn read_assert_runtime() {
+        let n = 1 << 20;
+
+        read_assert!(Placeholder {}, n, vec![0; n]);
+        read_assert!(Placeholder {}, vec![0; n]);
+        read_assert!(Placeholder {}, b"\0\0\0");
+    }
+
+    #[test]
+    #[should_panic]
+    fn read_assert_length_panic() {
+        read_assert!(Placeholder {}, 2, [0; 3]);
+    }
+
+    #[test]
+    fn read_assert_reborrow() {
+        fn read(reader: &mut Placeholder) {
+            read_assert!(reader, [0; 2]);
+        }
+
+        read(&mut Placeholder {});
+    }
+
+    #[test]
+    fn assert_read_ok() {
+        use std::io::Write;
+
+        let (mut local, mut remote) = channel();
+
+        local.write_all(&[0; 4]).unwrap();
+        assert_read(&mut remote, &[0; 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "received 2 of 4 bytes: [1, 2]")]
+    fn read_assert_timeout() {
+        use std::io::Write;
+
+        let (mut local, mut remote) = channel();
+
+        local.write_all(&[1, 2]).unwrap();
+        read_assert!(remote, [1, 2, 3, 4], timeout = Duration::from_millis(50));
+    }
+
+    #[test]
+    #[should_panic(expected = "read_assert! timed out after 50ms, received 0 of 1 bytes")]
+    fn read_assert_timeout_shared_ref() {
+        let (_local, remote) = channel();
+        let mut reader = &remote;
+
+        read_assert!(reader, [1], timeout = Duration::from_millis(50));
+    }
+
+    #[test]
+    fn read_assert_timeout_wrappers() {
+        use std::io::{BufReader, Write};
+
+        let (mut local, remote) = channel();
+
+        local.write_all(&[1, 2, 3]).unwrap();
+
+        let mut reader = BufReader::new(Box::new(remote));
+        read_assert!(reader, [1], timeout = Duration::from_secs(1));
+        read_assert!(&mut reader, [2, 3], timeout = Duration::from_secs(1));
+    }
+
+    #[test]
+    fn read_assert_zero_timeout() {
+        use std::io::Write;
+
+        let (mut local, mut remote) = channel();
+
+        local.write_all(&[1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(10));
+
+        read_assert!(remote, [1, 2], timeout = Duration::from_secs(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "read_assert! timed out after 0ns, received 0 of 1 bytes")]
+    fn read_assert_zero_timeout_empty() {
+        let (_local, mut remote) = channel();
+
+        read_assert!(remote, [1], timeout = Duration::from_secs(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "reached EOF after receiving 1 of 2 bytes")]
+    fn read_assert_eof() {
+        use std::io::Write;
+
+        let (mut local, mut remote) = channel();
+
+        local.write_all(&[1]).unwrap();
+        drop(local);
+
+        read_assert!(remote, 2, [1, 2]);
+    }
+
+    #[test]
+    fn read_assert_timeout_restored() {
+        use std::io::Write;
+
+        let (mut local, mut remote) = channel();
+
+        local.write_all(&[1]).unwrap();
+        read_assert!(remote, [1], timeout = Duration::from_secs(1));
+        read_assert!(remote, [], timeout = None);
+
+        assert_eq!(remote.read_timeout().unwrap(), None);
+    }
+
     macro_rules! test {
         () => {
             let (local, remote) = channel();
@@ -337,4 +917,64 @@ mod tests {
     fn channel_4() {
         test!();
     }
+
+    #[test]
+    fn channel_on_distinct() {
+        let (first_address, second_address) = (unused_address(), unused_address());
+        let (first, _) = channel_on(first_address);
+        let (second, _) = channel_on(second_address);
+
+        assert_eq!(first.peer_addr().unwrap(), first_address);
+        assert_eq!(second.peer_addr().unwrap(), second_address);
+    }
+
+    #[test]
+    fn try_channel_on_bind_err() {
+        let blocker = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = blocker.local_addr().unwrap();
+
+        match try_channel_on(address) {
+            Err(Error::Bind(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        drop(blocker);
+
+        let (local, _) = try_channel_on(address).unwrap();
+        assert_eq!(local.peer_addr().unwrap(), address);
+    }
+
+    #[test]
+    fn listener_addr_ephemeral() {
+        let address = listener_addr();
+
+        assert_ne!(address.port(), 0);
+        assert_eq!(address.ip(), Ipv4Addr::LOCALHOST);
+
+        let (local, _) = channel_on("127.0.0.1:0");
+        assert_eq!(local.peer_addr().unwrap(), address);
+    }
+
+    #[test]
+    fn channel_on_stray() {
+        use std::io::Read;
+
+        let address = unused_address();
+
+        // start the listener
+        drop(channel_on(address));
+
+        let mut stray = TcpStream::connect(address).unwrap();
+        let (local, remote) = channel_on(address);
+
+        assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+        assert_ne!(stray.local_addr().unwrap(), remote.peer_addr().unwrap());
+
+        // the stray connection was closed
+        let mut buf = [0; 1];
+        match stray.read(&mut buf) {
+            Ok(0) | Err(_) => {}
+            Ok(n) => panic!("read {} bytes from a stray connection", n),
+        }
+    }
 }
@@ -0,0 +1,538 @@
+use crate::diff::escape;
+use crate::read::{default_timeout, ReadTimeout};
+use crate::{bind, resolve, target, Error, Script, DEFAULT_ADDRESS};
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::Builder;
+use std::time::{Duration, Instant};
+
+/// A TCP server for testing clients which connect to an address themselves.
+///
+/// The server listens on a port chosen by the operating system
+/// and runs a handler for every accepted connection on its own thread.
+/// By default, the handler reads everything until EOF without replying.
+/// Everything the handlers read is recorded, see [`connections()`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{MockServer, Script};
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// let server = MockServer::builder()
+///     .script(Script::new().expect(b"PING\r\n").send(b"+PONG\r\n"))
+///     .start()
+///     .unwrap();
+///
+/// // the client under test
+/// for _ in 0..2 {
+///     let mut client = TcpStream::connect(server.addr()).unwrap();
+///     client.write_all(b"PING\r\n").unwrap();
+///
+///     let mut reply = Vec::new();
+///     client.read_to_end(&mut reply).unwrap();
+///     assert_eq!(reply, b"+PONG\r\n");
+/// }
+///
+/// let connections = server.connections();
+/// assert_eq!(connections.len(), 2);
+/// assert_eq!(connections[1].received(), b"PING\r\n");
+/// assert!(connections[1].error().is_none());
+/// ```
+///
+/// [`connections()`]: #method.connections
+pub struct MockServer {
+    address: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+/// Creates a [`MockServer`] with a custom handler or address.
+///
+/// [`MockServer`]: struct.MockServer.html
+#[derive(Clone, Debug)]
+pub struct MockServerBuilder {
+    address: SocketAddr,
+    handler: Handler,
+}
+
+type Closure = dyn Fn(&mut MockStream) -> io::Result<()> + Send + Sync;
+
+#[derive(Clone)]
+enum Handler {
+    Record,
+    Script(Arc<Script>),
+    Closure(Arc<Closure>),
+}
+
+impl fmt::Debug for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Handler::Record => f.write_str("Record"),
+            Handler::Script(script) => f.debug_tuple("Script").field(script).finish(),
+            Handler::Closure(_) => f.write_str("Closure(..)"),
+        }
+    }
+}
+
+impl Default for MockServerBuilder {
+    fn default() -> MockServerBuilder {
+        MockServerBuilder {
+            address: *DEFAULT_ADDRESS,
+            handler: Handler::Record,
+        }
+    }
+}
+
+impl MockServerBuilder {
+    /// Creates a new builder for a server which only records what it receives.
+    #[inline]
+    pub fn new() -> MockServerBuilder {
+        MockServerBuilder::default()
+    }
+
+    /// Sets the address to listen on, `127.0.0.1:0` by default.
+    ///
+    /// Fails if the address cannot be resolved.
+    pub fn address(mut self, address: impl ToSocketAddrs) -> Result<MockServerBuilder, Error> {
+        self.address = resolve(address)?;
+        Ok(self)
+    }
+
+    /// Runs `script` against every accepted connection.
+    #[inline]
+    pub fn script(mut self, script: Script) -> MockServerBuilder {
+        self.handler = Handler::Script(Arc::new(script));
+        self
+    }
+
+    /// Calls `handler` with every accepted connection.
+    ///
+    /// The connection is closed once the handler returns.
+    #[inline]
+    pub fn handler<F>(mut self, handler: F) -> MockServerBuilder
+    where
+        F: Fn(&mut MockStream) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.handler = Handler::Closure(Arc::new(handler));
+        self
+    }
+
+    /// Binds the listener and starts accepting connections on a background thread.
+    pub fn start(&self) -> Result<MockServer, Error> {
+        let listener = bind(self.address).map_err(Error::Bind)?;
+        let address = listener.local_addr().map_err(Error::Bind)?;
+
+        let shared = Arc::new(Shared {
+            connections: Mutex::new(Vec::new()),
+            changed: Condvar::new(),
+            stopped: AtomicBool::new(false),
+        });
+
+        let accepting = shared.clone();
+        let handler = self.handler.clone();
+
+        Builder::new()
+            .name(format!("tcp-test mock server ({})", address))
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if accepting.stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    if let Ok(stream) = stream {
+                        accepting.serve(stream, handler.clone());
+                    }
+                }
+            })
+            .map_err(Error::Thread)?;
+
+        Ok(MockServer { address, shared })
+    }
+}
+
+/// State shared with the server threads.
+struct Shared {
+    connections: Mutex<Vec<Connection>>,
+    changed: Condvar,
+    stopped: AtomicBool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Vec<Connection>> {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `handler` for `stream` on a new thread.
+    fn serve(self: &Arc<Shared>, stream: TcpStream, handler: Handler) {
+        let index = {
+            let mut connections = self.lock();
+
+            connections.push(Connection {
+                peer_addr: stream.peer_addr().ok(),
+                received: Vec::new(),
+                error: None,
+                finished: false,
+            });
+
+            self.changed.notify_all();
+
+            connections.len() - 1
+        };
+
+        let mut stream = MockStream {
+            stream,
+            shared: self.clone(),
+            index,
+        };
+        let shared = self.clone();
+
+        let spawned = Builder::new()
+            .name(String::from("tcp-test mock connection"))
+            .spawn(move || {
+                let error = match handler {
+                    Handler::Record => io::copy(&mut stream, &mut io::sink())
+                        .err()
+                        .map(|e| e.to_string()),
+                    Handler::Script(script) => script.run(&mut stream).err().map(|e| e.to_string()),
+                    Handler::Closure(handler) => handler(&mut stream).err().map(|e| e.to_string()),
+                };
+
+                drop(stream);
+                shared.finish(index, error);
+            });
+
+        if let Err(e) = spawned {
+            self.finish(index, Some(e.to_string()));
+        }
+    }
+
+    fn finish(&self, index: usize, error: Option<String>) {
+        let mut connections = self.lock();
+
+        connections[index].error = error;
+        connections[index].finished = true;
+
+        self.changed.notify_all();
+    }
+}
+
+impl MockServer {
+    /// Starts a server which only records what it receives,
+    /// listening on `127.0.0.1` on a port chosen by the operating system.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the listener cannot be bound,
+    /// see [`MockServerBuilder`] for a fallible version.
+    ///
+    /// [`MockServerBuilder`]: struct.MockServerBuilder.html
+    #[inline]
+    pub fn start() -> MockServer {
+        MockServerBuilder::new()
+            .start()
+            .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+    }
+
+    /// Returns a builder for a server with a custom handler or address.
+    #[inline]
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder::new()
+    }
+
+    /// Returns the address the server is listening on.
+    #[inline]
+    pub fn addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the number of connections accepted so far.
+    pub fn connection_count(&self) -> usize {
+        self.shared.lock().len()
+    }
+
+    /// Waits until the handlers of all connections accepted so far finished,
+    /// then returns the connections in the order they were accepted.
+    ///
+    /// Connections which the client already established,
+    /// but the server did not accept yet, are missing,
+    /// use [`wait_for_connections()`] if the number of connections is known.
+    /// Panics if the handlers do not finish within the [`default_timeout()`].
+    ///
+    /// [`wait_for_connections()`]: #method.wait_for_connections
+    /// [`default_timeout()`]: fn.default_timeout.html
+    #[track_caller]
+    pub fn connections(&self) -> Vec<Connection> {
+        self.wait_for_connections(0)
+    }
+
+    /// Waits until at least `n` connections were accepted and all their handlers finished,
+    /// then returns the connections in the order they were accepted.
+    ///
+    /// Panics if that does not happen within the [`default_timeout()`].
+    ///
+    /// [`default_timeout()`]: fn.default_timeout.html
+    #[track_caller]
+    pub fn wait_for_connections(&self, n: usize) -> Vec<Connection> {
+        let timeout = default_timeout();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut connections = self.shared.lock();
+
+        while connections.len() < n || connections.iter().any(|c| !c.finished) {
+            connections = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+
+                    if remaining == Duration::from_secs(0) {
+                        panic!(
+                            "MockServer timed out after {:?} waiting for {} connections to finish, \
+                             {} of {} accepted connections finished",
+                            timeout.unwrap_or_default(),
+                            n.max(connections.len()),
+                            connections.iter().filter(|c| c.finished).count(),
+                            connections.len()
+                        );
+                    }
+
+                    self.shared
+                        .changed
+                        .wait_timeout(connections, remaining)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .shared
+                    .changed
+                    .wait(connections)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+
+        connections.clone()
+    }
+}
+
+impl fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockServer")
+            .field("address", &self.address)
+            .field("connections", &self.connection_count())
+            .finish()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+
+        // wakes up the accepting thread, which then stops
+        let _ = TcpStream::connect(target(self.address));
+    }
+}
+
+/// A connection accepted by a [`MockServer`].
+///
+/// [`MockServer`]: struct.MockServer.html
+#[derive(Clone)]
+pub struct Connection {
+    peer_addr: Option<SocketAddr>,
+    received: Vec<u8>,
+    error: Option<String>,
+    finished: bool,
+}
+
+impl Connection {
+    /// Returns the address of the client, if it could be determined.
+    #[inline]
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Returns everything the handler read from the client.
+    #[inline]
+    pub fn received(&self) -> &[u8] {
+        &self.received
+    }
+
+    /// Returns why the handler failed, like the failed step of a script.
+    #[inline]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("peer_addr", &self.peer_addr)
+            .field("received", &format_args!("b\"{}\"", escape(&self.received)))
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+/// The stream passed to the handler of a [`MockServer`],
+/// recording everything read from it.
+///
+/// [`MockServer`]: struct.MockServer.html
+pub struct MockStream {
+    stream: TcpStream,
+    shared: Arc<Shared>,
+    index: usize,
+}
+
+impl MockStream {
+    /// Returns the underlying TCP stream.
+    ///
+    /// Reading from it directly bypasses the recording.
+    #[inline]
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+}
+
+impl fmt::Debug for MockStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockStream")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stream.read(buf)?;
+
+        self.shared.lock()[self.index]
+            .received
+            .extend_from_slice(&buf[..n]);
+
+        Ok(n)
+    }
+}
+
+impl Write for MockStream {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl ReadTimeout for MockStream {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.stream.read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        Some(self.stream.peek(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_assert;
+
+    #[test]
+    fn mock_records() {
+        let server = MockServer::start();
+
+        for message in &[&b"first"[..], b"second"] {
+            let mut client = TcpStream::connect(server.addr()).unwrap();
+            client.write_all(message).unwrap();
+        }
+
+        let connections = server.wait_for_connections(2);
+
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections[0].received(), b"first");
+        assert_eq!(connections[1].received(), b"second");
+        assert!(connections.iter().all(|c| c.error().is_none()));
+    }
+
+    #[test]
+    fn mock_handler() {
+        let server = MockServer::builder()
+            .handler(|stream| {
+                let mut buf = [0; 4];
+                stream.read_exact(&mut buf)?;
+                buf.reverse();
+                stream.write_all(&buf)
+            })
+            .start()
+            .unwrap();
+
+        let mut client = TcpStream::connect(server.addr()).unwrap();
+        client.write_all(b"abcd").unwrap();
+        read_assert!(client, b"dcba");
+
+        assert_eq!(server.connection_count(), 1);
+        assert_eq!(server.connections()[0].received(), b"abcd");
+    }
+
+    #[test]
+    fn mock_script_error() {
+        let server = MockServer::builder()
+            .script(Script::new().expect(b"HELO"))
+            .start()
+            .unwrap();
+
+        let mut client = TcpStream::connect(server.addr()).unwrap();
+        client.write_all(b"EHLO").unwrap();
+
+        let connections = server.wait_for_connections(1);
+        let error = connections[0].error().unwrap();
+
+        assert!(error.starts_with("script step 1 of 1, expect(b\"HELO\"), failed"));
+        assert_eq!(
+            connections[0].peer_addr(),
+            Some(client.local_addr().unwrap())
+        );
+    }
+
+    #[test]
+    fn mock_address() {
+        let server = MockServer::builder()
+            .address("127.0.0.1:0")
+            .unwrap()
+            .start()
+            .unwrap();
+
+        assert_ne!(server.addr().port(), 0);
+        assert_eq!(server.connection_count(), 0);
+        assert!(server.connections().is_empty());
+    }
+
+    #[test]
+    fn mock_stops() {
+        let server = MockServer::start();
+        let address = server.addr();
+
+        drop(server);
+
+        // the listener is closed once the accepting thread noticed
+        let start = Instant::now();
+        while TcpStream::connect(address).is_ok() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use crate::diff::{escape, hex_diff};
+use crate::read::{default_timeout, read_delimited, read_window, Outcome, ReadTimeout};
+
+use std::fmt;
+use std::io::Read;
+use std::time::Duration;
+
+/// What the bytes read by [`assert_read_until()`] and [`assert_read_for()`] are compared with.
+///
+/// More variants may be added, like the `Regex` one of the `regex` feature.
+///
+/// [`assert_read_until()`]: fn.assert_read_until.html
+/// [`assert_read_for()`]: fn.assert_read_for.html
+#[non_exhaustive]
+pub enum Pattern<'a> {
+    /// The bytes start with the given prefix.
+    Prefix(&'a [u8]),
+
+    /// The bytes match the given regular expression somewhere,
+    /// use `^` and `$` to match all of them.
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    Regex(&'a regex::bytes::Regex),
+
+    /// The closure returns `true` for the bytes.
+    Predicate(&'a dyn Fn(&[u8]) -> bool),
+}
+
+impl Pattern<'_> {
+    /// Returns whether `bytes` match the pattern.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        match self {
+            Pattern::Prefix(prefix) => bytes.starts_with(prefix),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => regex.is_match(bytes),
+            Pattern::Predicate(predicate) => predicate(bytes),
+        }
+    }
+}
+
+impl fmt::Debug for Pattern<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Prefix(prefix) => write!(f, "Prefix(b\"{}\")", escape(prefix)),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => write!(f, "Regex({})", regex),
+            Pattern::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+/// Reads from `reader` until `delimiter` is received and returns the bytes before it.
+///
+/// Only reads up to the delimiter, so the following bytes can be read afterwards.
+/// Panics if reading fails or does not complete within the [`default_timeout()`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{channel, read_until};
+/// use std::io::Write;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// local.write_all(b"PING\r\nPING again\r\n").unwrap();
+///
+/// assert_eq!(read_until(&mut remote, b"\r\n"), b"PING");
+/// assert_eq!(read_until(&mut remote, b"\r\n"), b"PING again");
+/// ```
+///
+/// [`default_timeout()`]: fn.default_timeout.html
+#[track_caller]
+pub fn read_until<R>(reader: &mut R, delimiter: &[u8]) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    read_until_timeout(reader, delimiter, default_timeout())
+}
+
+/// Like [`read_until()`], but with the given timeout instead of the default one.
+///
+/// [`read_until()`]: fn.read_until.html
+#[track_caller]
+pub fn read_until_timeout<R>(reader: &mut R, delimiter: &[u8], timeout: Option<Duration>) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    delimited("read_until()", reader, delimiter, timeout)
+}
+
+/// Reads from `reader` until `delimiter` is received
+/// and compares the bytes before it with `pattern`.
+///
+/// Returns the bytes before the delimiter for further checks.
+/// Panics if they do not match, or reading fails or does not complete
+/// within the [`default_timeout()`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_read_until, channel, Pattern};
+/// use std::io::Write;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// local.write_all(b"250-smtp.example.com\r\n250 SIZE 1024\r\n").unwrap();
+///
+/// assert_read_until(&mut remote, b"\r\n", Pattern::Prefix(b"250-"));
+///
+/// let line = assert_read_until(&mut remote, b"\r\n", Pattern::Predicate(&|line| line.len() < 20));
+/// assert_eq!(line, b"250 SIZE 1024");
+/// ```
+///
+/// [`default_timeout()`]: fn.default_timeout.html
+#[track_caller]
+pub fn assert_read_until<R>(reader: &mut R, delimiter: &[u8], pattern: Pattern) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    assert_read_until_timeout(reader, delimiter, pattern, default_timeout())
+}
+
+/// Like [`assert_read_until()`], but with the given timeout instead of the default one.
+///
+/// [`assert_read_until()`]: fn.assert_read_until.html
+#[track_caller]
+pub fn assert_read_until_timeout<R>(
+    reader: &mut R,
+    delimiter: &[u8],
+    pattern: Pattern,
+    timeout: Option<Duration>,
+) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    let name = "assert_read_until()";
+    let bytes = delimited(name, reader, delimiter, timeout);
+
+    compare(name, bytes, pattern)
+}
+
+/// Reads from `reader` until `window` passes or EOF is reached,
+/// and returns all bytes received.
+///
+/// This collects replies which do not end with a delimiter.
+/// Reaching the end of `window` is not a failure, panics only if reading fails.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{channel, read_for};
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// local.write_all(b"> ").unwrap();
+///
+/// assert_eq!(read_for(&mut remote, Duration::from_millis(50)), b"> ");
+/// ```
+#[track_caller]
+pub fn read_for<R>(reader: &mut R, window: Duration) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    collected("read_for()", reader, window)
+}
+
+/// Reads from `reader` until `window` passes or EOF is reached,
+/// and compares all bytes received with `pattern`.
+///
+/// Returns the bytes for further checks, like [`assert_read_until()`] does.
+/// Panics if they do not match or reading fails.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_read_for, channel, Pattern};
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// local.write_all(b"Password: ").unwrap();
+///
+/// assert_read_for(&mut remote, Duration::from_millis(50), Pattern::Prefix(b"Password"));
+/// ```
+///
+/// [`assert_read_until()`]: fn.assert_read_until.html
+#[track_caller]
+pub fn assert_read_for<R>(reader: &mut R, window: Duration, pattern: Pattern) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    let name = "assert_read_for()";
+    let bytes = collected(name, reader, window);
+
+    compare(name, bytes, pattern)
+}
+
+/// Returns `bytes` if they match `pattern`, panics otherwise.
+#[track_caller]
+fn compare(name: &str, bytes: Vec<u8>, pattern: Pattern) -> Vec<u8> {
+    if pattern.matches(&bytes) {
+        return bytes;
+    }
+
+    match pattern {
+        Pattern::Prefix(prefix) => panic!(
+            "{} received bytes which do not start with the prefix, {}",
+            name,
+            hex_diff(prefix, &bytes[..prefix.len().min(bytes.len())])
+                .unwrap_or_default()
+                .trim_end()
+        ),
+        #[cfg(feature = "regex")]
+        Pattern::Regex(regex) => panic!(
+            "{} received b\"{}\", which does not match the regex `{}`",
+            name,
+            escape(&bytes),
+            regex
+        ),
+        Pattern::Predicate(_) => panic!(
+            "{} received b\"{}\", which does not satisfy the predicate",
+            name,
+            escape(&bytes)
+        ),
+    }
+}
+
+/// Reads until `window` passes or EOF is reached, panicking if reading fails.
+#[track_caller]
+fn collected<R>(name: &str, reader: &mut R, window: Duration) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    let mut buf = Vec::new();
+
+    match read_window(reader, &mut buf, window) {
+        Outcome::Error(e) => panic!(
+            "failed to read in {}: {}, received {} bytes: b\"{}\"",
+            name,
+            e,
+            buf.len(),
+            escape(&buf)
+        ),
+        _ => buf,
+    }
+}
+
+/// Reads until `delimiter` and returns the bytes before it, panicking if that fails.
+#[track_caller]
+fn delimited<R>(name: &str, reader: &mut R, delimiter: &[u8], timeout: Option<Duration>) -> Vec<u8>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    let mut buf = Vec::new();
+
+    match read_delimited(reader, &mut buf, delimiter, timeout) {
+        Outcome::Complete => {}
+        Outcome::Eof => panic!(
+            "{} reached EOF before b\"{}\", received {} bytes: b\"{}\"",
+            name,
+            escape(delimiter),
+            buf.len(),
+            escape(&buf)
+        ),
+        Outcome::TimedOut(timeout) => panic!(
+            "{} timed out after {:?} waiting for b\"{}\", received {} bytes: b\"{}\"",
+            name,
+            timeout,
+            escape(delimiter),
+            buf.len(),
+            escape(&buf)
+        ),
+        Outcome::Error(e) => panic!(
+            "failed to read in {}: {}, received {} bytes: b\"{}\"",
+            name,
+            e,
+            buf.len(),
+            escape(&buf)
+        ),
+    }
+
+    buf.truncate(buf.len() - delimiter.len());
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel;
+
+    use std::io::Write;
+
+    #[test]
+    fn read_until_leaves_rest() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"a\r\r\nb\r\nc").unwrap();
+
+        assert_eq!(read_until(&mut remote, b"\r\n"), b"a\r");
+        assert_eq!(read_until(&mut remote, b"\r\n"), b"b");
+
+        crate::read_assert!(remote, b"c");
+    }
+
+    #[test]
+    fn read_until_overlap() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"xabababcy").unwrap();
+
+        assert_eq!(read_until(&mut remote, b"ababc"), b"xab");
+
+        crate::read_assert!(remote, b"y");
+    }
+
+    #[test]
+    fn read_until_split_delimiter() {
+        let (mut local, mut remote) = channel();
+
+        let writer = std::thread::spawn(move || {
+            local.write_all(b"ab\r").unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+            local.write_all(b"\nc").unwrap();
+            local
+        });
+
+        assert_eq!(read_until(&mut remote, b"\r\n"), b"ab");
+
+        drop(writer.join().unwrap());
+        crate::read_assert!(remote, b"c");
+    }
+
+    #[test]
+    fn read_until_without_peek() {
+        let (mut local, remote) = channel();
+        let mut remote = crate::AbortAfter::new(remote, 100);
+
+        local.write_all(b"xabababcy").unwrap();
+
+        assert_eq!(read_until(&mut remote, b"ababc"), b"xab");
+
+        crate::read_assert!(remote, b"y");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "timed out after 100ms waiting for b\"\\r\\n\", received 3 bytes: b\"abc\""
+    )]
+    fn read_until_timeout_panic() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"abc").unwrap();
+
+        read_until_timeout(&mut remote, b"\r\n", Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    #[should_panic(expected = "reached EOF before b\"\\n\", received 2 bytes: b\"ab\"")]
+    fn read_until_eof() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"ab").unwrap();
+        drop(local);
+
+        read_until(&mut remote, b"\n");
+    }
+
+    #[test]
+    fn assert_read_until_prefix() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"+OK ready\n").unwrap();
+
+        let line = assert_read_until(&mut remote, b"\n", Pattern::Prefix(b"+OK"));
+        assert_eq!(line, b"+OK ready");
+    }
+
+    #[test]
+    #[should_panic(expected = "do not start with the prefix, first difference at offset 0")]
+    fn assert_read_until_prefix_panic() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"-ERR\n").unwrap();
+
+        assert_read_until(&mut remote, b"\n", Pattern::Prefix(b"+OK"));
+    }
+
+    #[test]
+    #[should_panic(expected = "received b\"-ERR\", which does not satisfy the predicate")]
+    fn assert_read_until_predicate_panic() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"-ERR\n").unwrap();
+
+        assert_read_until(
+            &mut remote,
+            b"\n",
+            Pattern::Predicate(&|line| line[0] == b'+'),
+        );
+    }
+
+    #[test]
+    fn read_for_window() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"no newline").unwrap();
+
+        let start = std::time::Instant::now();
+        assert_eq!(
+            read_for(&mut remote, Duration::from_millis(50)),
+            b"no newline"
+        );
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        drop(local);
+        assert_eq!(read_for(&mut remote, Duration::from_secs(5)), b"");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assert_read_for() received bytes which do not start with the prefix"
+    )]
+    fn assert_read_for_panic() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"-ERR").unwrap();
+
+        assert_read_for(
+            &mut remote,
+            Duration::from_millis(50),
+            Pattern::Prefix(b"+OK"),
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn assert_read_until_regex() {
+        let (mut local, mut remote) = channel();
+        let regex = regex::bytes::Regex::new(r"^HTTP/1\.[01] 200 ").unwrap();
+
+        local
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+
+        assert_read_until(&mut remote, b"\r\n", Pattern::Regex(&regex));
+        assert_read_until(&mut remote, b"\r\n\r\n", Pattern::Prefix(b"Content-Length"));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    #[should_panic(expected = "which does not match the regex `^[0-9]+$`")]
+    fn assert_read_until_regex_panic() {
+        let (mut local, mut remote) = channel();
+        let regex = regex::bytes::Regex::new("^[0-9]+$").unwrap();
+
+        local.write_all(b"12a\n").unwrap();
+
+        assert_read_until(&mut remote, b"\n", Pattern::Regex(&regex));
+    }
+}
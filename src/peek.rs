@@ -0,0 +1,247 @@
+use crate::diff::escape;
+use crate::read::{ReadTimeout, MIN_TIMEOUT};
+use crate::Fragmented;
+
+use std::io::{self, ErrorKind};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+/// Streams which can look at received data without consuming it.
+///
+/// This is what [`assert_no_data!`] uses to wait for data.
+///
+/// [`assert_no_data!`]: macro.assert_no_data.html
+pub trait Peek: ReadTimeout {
+    /// Receives data into `buf` without removing it from the queue,
+    /// returning the number of bytes received.
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl Peek for TcpStream {
+    #[inline]
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        TcpStream::peek(self, buf)
+    }
+}
+
+#[cfg(unix)]
+impl Peek for UnixStream {
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::mem::MaybeUninit;
+
+        // SAFETY: initialized bytes are valid `MaybeUninit<u8>`,
+        // and the kernel only writes initialized bytes into the buffer
+        let buf = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+
+        socket2::SockRef::from(self).peek(buf)
+    }
+}
+
+impl<S: Peek> Peek for Fragmented<S> {
+    #[inline]
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.get_ref().peek(buf)
+    }
+}
+
+/// Asserts that no data arrives at `stream` within the given duration.
+///
+/// This is the function form of [`assert_no_data!`].
+/// Data is not consumed, so it can still be read if the assertion passes.
+/// Reaching EOF also counts as no data, use [`assert_eof!`] to check for it.
+///
+/// # Panics
+///
+/// Panics with the unexpected bytes if data arrives, or if peeking fails.
+///
+/// [`assert_no_data!`]: macro.assert_no_data.html
+/// [`assert_eof!`]: macro.assert_eof.html
+#[track_caller]
+pub fn assert_no_data<S: Peek + ?Sized>(stream: &S, within: Duration) {
+    let name = "assert_no_data!";
+
+    // large enough to show what arrived, small enough for the panic message
+    let mut buf = [0; 1024];
+
+    let previous = stream
+        .read_timeout()
+        .unwrap_or_else(|e| panic!("failed to peek in {}: {}", name, e));
+
+    // a zero timeout means blocking indefinitely, so a zero window waits a moment
+    stream
+        .set_read_timeout(Some(within.max(MIN_TIMEOUT)))
+        .unwrap_or_else(|e| panic!("failed to peek in {}: {}", name, e));
+
+    let result = loop {
+        match stream.peek(&mut buf) {
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            result => break result,
+        }
+    };
+
+    let _ = stream.set_read_timeout(previous);
+
+    match result {
+        Ok(0) => {}
+        Ok(n) => panic!(
+            "{} expected no data within {:?}, but received {} bytes: b\"{}\"",
+            name,
+            within,
+            n,
+            escape(&buf[..n])
+        ),
+        Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+        Err(e) => panic!("failed to peek in {}: {}", name, e),
+    }
+}
+
+/// Asserts that no data arrives at `$stream` within the duration given as `within`.
+///
+/// Waits for the whole duration if the peer stays silent.
+/// Data is not consumed, so it can still be read if the assertion passes.
+/// Reaching EOF also counts as no data, use [`assert_eof!`] to check for it.
+/// Panics with the unexpected bytes if data arrives.
+///
+/// `$stream` must implement [`Peek`], also see the function form [`assert_no_data()`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_no_data, channel, read_assert};
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// assert_no_data!(remote, within = Duration::from_millis(50));
+///
+/// local.write_all(b"late").unwrap();
+/// read_assert!(remote, b"late");
+/// ```
+///
+/// ```should_panic
+/// use tcp_test::{assert_no_data, channel};
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// let (mut local, remote) = channel();
+///
+/// local.write_all(b"-ERR").unwrap();
+///
+/// // panics: "assert_no_data! expected no data within 1s, but received 4 bytes: b\"-ERR\""
+/// assert_no_data!(remote, within = Duration::from_secs(1));
+/// ```
+///
+/// [`assert_eof!`]: macro.assert_eof.html
+/// [`Peek`]: trait.Peek.html
+/// [`assert_no_data()`]: fn.assert_no_data.html
+#[macro_export]
+macro_rules! assert_no_data {
+    ($stream:expr, within = $within:expr) => {{
+        $crate::assert_no_data(&$stream, $within);
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{channel, read_assert, Fragments};
+
+    use std::io::Write;
+    use std::panic::AssertUnwindSafe;
+    use std::time::Instant;
+
+    #[test]
+    fn no_data_waits() {
+        let (_local, remote) = channel();
+        let timeout = Some(Duration::from_secs(3));
+        remote.set_read_timeout(timeout).unwrap();
+
+        let start = Instant::now();
+        assert_no_data!(remote, within = Duration::from_millis(100));
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(remote.read_timeout().unwrap(), timeout);
+    }
+
+    #[test]
+    fn no_data_zero() {
+        let (mut local, mut remote) = channel();
+
+        assert_no_data!(remote, within = Duration::from_secs(0));
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            local.write_all(b"data").unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+            assert_no_data(&remote, Duration::from_secs(0));
+        }));
+
+        assert!(result.is_err());
+        read_assert!(remote, b"data");
+    }
+
+    #[test]
+    fn no_data_zero_keeps_mode() {
+        use std::io::Read;
+
+        let (_local, mut remote) = channel();
+        remote.set_nonblocking(true).unwrap();
+
+        assert_no_data!(remote, within = Duration::from_secs(0));
+
+        let error = remote.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn no_data_eof() {
+        let (local, remote) = channel();
+
+        drop(local);
+
+        assert_no_data!(remote, within = Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected no data within 1s, but received 2 bytes: b\"\\x00\\n\"")]
+    fn no_data_panic() {
+        let (mut local, remote) = channel();
+
+        local.write_all(b"\0\n").unwrap();
+
+        assert_no_data!(remote, within = Duration::from_secs(1));
+    }
+
+    #[test]
+    fn no_data_not_consumed() {
+        let (mut local, mut remote) = channel();
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            local.write_all(b"data").unwrap();
+            assert_no_data(&remote, Duration::from_secs(1));
+        }));
+
+        assert!(result.is_err());
+        read_assert!(remote, b"data");
+    }
+
+    #[test]
+    fn no_data_fragmented() {
+        let (_local, remote) = Fragmented::channel(Fragments::EveryByte);
+
+        assert_no_data!(remote, within = Duration::from_millis(10));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[should_panic(expected = "received 3 bytes: b\"abc\"")]
+    fn no_data_unix() {
+        let (mut local, remote) = crate::unix::channel();
+
+        local.write_all(b"abc").unwrap();
+
+        assert_no_data!(remote, within = Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,601 @@
+use crate::diff::hex_diff;
+#[cfg(unix)]
+use crate::peek::Peek;
+use crate::{AbortAfter, Fragmented};
+
+use lazy_static::lazy_static;
+
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    /// The timeout used by `read_assert!` if none is given.
+    static ref DEFAULT_TIMEOUT: Mutex<Option<Duration>> = Mutex::new(Some(Duration::from_secs(10)));
+}
+
+/// Returns the timeout used by [`read_assert!`] if none is given, 10 seconds by default.
+///
+/// [`read_assert!`]: macro.read_assert.html
+pub fn default_timeout() -> Option<Duration> {
+    *DEFAULT_TIMEOUT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sets the timeout used by [`read_assert!`] if none is given,
+/// `None` meaning it blocks indefinitely.
+///
+/// This affects all threads.
+///
+/// [`read_assert!`]: macro.read_assert.html
+pub fn set_default_timeout(timeout: Option<Duration>) {
+    *DEFAULT_TIMEOUT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = timeout;
+}
+
+/// Streams with a read timeout.
+///
+/// [`read_assert!`] only times out on resources implementing this trait,
+/// other resources are read without a timeout.
+/// Besides the streams of this crate, it is implemented for references,
+/// `Box` and `BufReader` around them.
+///
+/// [`read_assert!`]: macro.read_assert.html
+pub trait ReadTimeout {
+    /// Returns the current read timeout.
+    fn read_timeout(&self) -> io::Result<Option<Duration>>;
+
+    /// Sets the read timeout, `None` meaning reads block indefinitely.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Peeks like [`Peek::peek()`](trait.Peek.html) if supported, not public API.
+    #[doc(hidden)]
+    #[inline]
+    fn __peek(&self, _buf: &mut [u8]) -> Option<io::Result<usize>> {
+        None
+    }
+}
+
+impl<T: ReadTimeout + ?Sized> ReadTimeout for &T {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        (**self).read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        (**self).__peek(buf)
+    }
+}
+
+impl<T: ReadTimeout + ?Sized> ReadTimeout for &mut T {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        (**self).read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        (**self).__peek(buf)
+    }
+}
+
+impl<T: ReadTimeout + ?Sized> ReadTimeout for Box<T> {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        (**self).read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        (**self).__peek(buf)
+    }
+}
+
+// peeking would skip the buffered bytes, so delimiters are searched byte by byte
+impl<T: ReadTimeout> ReadTimeout for BufReader<T> {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.get_ref().read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+}
+
+impl ReadTimeout for TcpStream {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        Some(TcpStream::peek(self, buf))
+    }
+}
+
+#[cfg(unix)]
+impl ReadTimeout for UnixStream {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        UnixStream::read_timeout(self)
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        Some(Peek::peek(self, buf))
+    }
+}
+
+impl<S: ReadTimeout> ReadTimeout for Fragmented<S> {
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.get_ref().read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+
+    #[inline]
+    fn __peek(&self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        self.get_ref().__peek(buf)
+    }
+}
+
+impl ReadTimeout for AbortAfter {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        match self.get_ref() {
+            Some(stream) => TcpStream::read_timeout(stream),
+            None => Ok(None),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self.get_ref() {
+            Some(stream) => TcpStream::set_read_timeout(stream, timeout),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<C, S> ReadTimeout for rustls::StreamOwned<C, S>
+where
+    S: Read + io::Write + ReadTimeout,
+{
+    #[inline]
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.sock.read_timeout()
+    }
+
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+/// The shortest timeout set on a reader, as a zero timeout means blocking indefinitely.
+pub(crate) const MIN_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// How reading `n` bytes ended.
+pub(crate) enum Outcome {
+    Complete,
+    Eof,
+    TimedOut(Duration),
+    Error(io::Error),
+}
+
+/// Reads until `buf` holds `n` bytes, EOF is reached, or `timeout` passes.
+pub(crate) fn read_full<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    n: usize,
+    timeout: Option<Duration>,
+) -> Outcome
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    read_while(reader, buf, timeout, |buf| n.saturating_sub(buf.len()))
+}
+
+/// Reads until `buf` holds `n` bytes or EOF is reached, without a timeout.
+pub(crate) fn read_blocking<R: Read + ?Sized>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    n: usize,
+) -> Outcome {
+    read_while_blocking(reader, buf, |buf| n.saturating_sub(buf.len()))
+}
+
+/// Reads until EOF is reached or `window` passes, which is not a failure here.
+pub(crate) fn read_window<R>(reader: &mut R, buf: &mut Vec<u8>, window: Duration) -> Outcome
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    read_while(reader, buf, Some(window), |_| usize::MAX)
+}
+
+/// Reads until `buf` ends with `delimiter`, EOF is reached, or `timeout` passes.
+///
+/// Never reads past the delimiter, so the following bytes stay in the stream.
+/// Readers which can peek are searched a block at a time, all others byte by byte.
+pub(crate) fn read_delimited<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    delimiter: &[u8],
+    timeout: Option<Duration>,
+) -> Outcome
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut peeked = vec![0; 8192];
+    let mut attempted = false;
+
+    while !buf.ends_with(delimiter) {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        let result = match remaining {
+            Some(remaining) if remaining == Duration::from_secs(0) && attempted => {
+                return Outcome::TimedOut(timeout.unwrap_or_default());
+            }
+            Some(remaining) => peek_timeout(reader, &mut peeked, remaining),
+            None => reader.__peek(&mut peeked),
+        };
+
+        attempted = true;
+
+        let n = match result {
+            // peeking is either supported or not, so nothing was read yet
+            None => return read_bytewise(reader, buf, delimiter, timeout),
+            Some(Ok(0)) => return Outcome::Eof,
+            Some(Ok(n)) => n,
+            Some(Err(e)) if e.kind() == ErrorKind::Interrupted => continue,
+            Some(Err(e))
+                if timeout.is_some()
+                    && (e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut) =>
+            {
+                return Outcome::TimedOut(timeout.unwrap_or_default());
+            }
+            Some(Err(e)) => return Outcome::Error(e),
+        };
+
+        // the delimiter may begin in the bytes read before
+        let start = buf.len().saturating_sub(delimiter.len() - 1);
+        let mut window = buf[start..].to_vec();
+        window.extend_from_slice(&peeked[..n]);
+
+        let len = window
+            .windows(delimiter.len())
+            .position(|bytes| bytes == delimiter)
+            .map_or(n, |position| {
+                position + delimiter.len() - (buf.len() - start)
+            });
+
+        // the peeked bytes already arrived, so this does not wait
+        match read_full(reader, buf, buf.len() + len, remaining) {
+            Outcome::Complete => {}
+            Outcome::TimedOut(_) => return Outcome::TimedOut(timeout.unwrap_or_default()),
+            outcome => return outcome,
+        }
+    }
+
+    Outcome::Complete
+}
+
+/// Peeks into `buf`, waiting for `timeout` at most.
+fn peek_timeout<R>(reader: &R, buf: &mut [u8], timeout: Duration) -> Option<io::Result<usize>>
+where
+    R: ReadTimeout + ?Sized,
+{
+    let previous = match reader.read_timeout() {
+        Ok(previous) => previous,
+        Err(e) => return Some(Err(e)),
+    };
+
+    if let Err(e) = reader.set_read_timeout(Some(timeout.max(MIN_TIMEOUT))) {
+        return Some(Err(e));
+    }
+
+    let result = reader.__peek(buf);
+    let _ = reader.set_read_timeout(previous);
+
+    result
+}
+
+/// Like `read_delimited()`, but reads only as many bytes as the delimiter can be away.
+fn read_bytewise<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    delimiter: &[u8],
+    timeout: Option<Duration>,
+) -> Outcome
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    read_while(reader, buf, timeout, |buf| {
+        if buf.ends_with(delimiter) {
+            return 0;
+        }
+
+        // the delimiter cannot end earlier than after this many bytes
+        let overlap = (1..delimiter.len())
+            .rev()
+            .find(|&i| buf.ends_with(&delimiter[..i]))
+            .unwrap_or(0);
+
+        delimiter.len() - overlap
+    })
+}
+
+/// Reads as many bytes as `want` returns for the current buffer until it returns zero,
+/// EOF is reached, or `timeout` passes.
+fn read_while<R, F>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    timeout: Option<Duration>,
+    mut want: F,
+) -> Outcome
+where
+    R: Read + ReadTimeout + ?Sized,
+    F: FnMut(&[u8]) -> usize,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return read_while_blocking(reader, buf, want),
+    };
+
+    let previous = match reader.read_timeout() {
+        Ok(previous) => previous,
+        Err(e) => return Outcome::Error(e),
+    };
+
+    let deadline = Instant::now() + timeout;
+    let mut chunk = Vec::new();
+    let mut attempted = false;
+
+    let outcome = loop {
+        let len = want(buf).min(8192);
+
+        if len == 0 {
+            break Outcome::Complete;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        // always read once, so even a zero timeout picks up data which already arrived
+        if remaining == Duration::from_secs(0) && attempted {
+            break Outcome::TimedOut(timeout);
+        }
+
+        attempted = true;
+
+        // a zero timeout means blocking indefinitely, so wait at least a moment
+        if let Err(e) = reader.set_read_timeout(Some(remaining.max(MIN_TIMEOUT))) {
+            break Outcome::Error(e);
+        }
+
+        chunk.resize(len, 0);
+
+        match reader.read(&mut chunk[..len]) {
+            Ok(0) => break Outcome::Eof,
+            Ok(read) => buf.extend_from_slice(&chunk[..read]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                break Outcome::TimedOut(timeout)
+            }
+            Err(e) => break Outcome::Error(e),
+        }
+    };
+
+    let _ = reader.set_read_timeout(previous);
+
+    outcome
+}
+
+/// Like `read_while()`, but without a timeout.
+fn read_while_blocking<R, F>(reader: &mut R, buf: &mut Vec<u8>, mut want: F) -> Outcome
+where
+    R: Read + ?Sized,
+    F: FnMut(&[u8]) -> usize,
+{
+    loop {
+        let len = want(buf);
+
+        if len == 0 {
+            return Outcome::Complete;
+        }
+
+        let filled = buf.len();
+        buf.resize(filled + len, 0);
+
+        let result = reader.read(&mut buf[filled..]);
+        buf.truncate(filled + *result.as_ref().unwrap_or(&0));
+
+        match result {
+            Ok(0) => return Outcome::Eof,
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Outcome::Error(e),
+        }
+    }
+}
+
+/// Panics if reading did not complete or `buf` differs from `expected`.
+#[track_caller]
+pub(crate) fn check(name: &str, buf: &[u8], n: usize, expected: &[u8], outcome: Outcome) {
+    if let Some(message) = mismatch(name, buf, n, expected, outcome) {
+        panic!("{}", message);
+    }
+}
+
+/// Describes why reading did not complete or `buf` differs from `expected`,
+/// or returns `None` if it matches.
+pub(crate) fn mismatch(
+    name: &str,
+    buf: &[u8],
+    n: usize,
+    expected: &[u8],
+    outcome: Outcome,
+) -> Option<String> {
+    match outcome {
+        Outcome::Complete => hex_diff(expected, buf)
+            .map(|diff| format!("{} buffers are not equal, {}", name, diff.trim_end())),
+        Outcome::Eof => Some(format!(
+            "{} reached EOF after receiving {} of {} bytes: {:?}",
+            name,
+            buf.len(),
+            n,
+            buf
+        )),
+        Outcome::TimedOut(timeout) => Some(format!(
+            "{} timed out after {:?}, received {} of {} bytes: {:?}",
+            name,
+            timeout,
+            buf.len(),
+            n,
+            buf
+        )),
+        Outcome::Error(e) => Some(format!(
+            "failed to read in {}: {}, received {} of {} bytes: {:?}",
+            name,
+            e,
+            buf.len(),
+            n,
+            buf
+        )),
+    }
+}
+
+/// Reads `expected.len()` bytes from `reader` and compares them with `expected`.
+///
+/// This is the function form of [`read_assert!`], using the [`default_timeout()`].
+/// Panics if the bytes are not equal, reading fails or times out.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_read, channel};
+/// use std::io::Write;
+///
+/// let (mut local, mut remote) = channel();
+/// let expected = vec![7; 100_000];
+///
+/// std::thread::spawn(move || local.write_all(&[7; 100_000]).unwrap());
+///
+/// assert_read(&mut remote, &expected);
+/// ```
+///
+/// [`read_assert!`]: macro.read_assert.html
+/// [`default_timeout()`]: fn.default_timeout.html
+#[track_caller]
+pub fn assert_read<R>(reader: &mut R, expected: &[u8])
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    assert_read_timeout(reader, expected, default_timeout());
+}
+
+/// Like [`assert_read()`], but with the given timeout instead of the default one.
+///
+/// [`assert_read()`]: fn.assert_read.html
+#[track_caller]
+pub fn assert_read_timeout<R>(reader: &mut R, expected: &[u8], timeout: Option<Duration>)
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    let mut buf = Vec::new();
+    let outcome = read_full(reader, &mut buf, expected.len(), timeout);
+
+    check("assert_read()", &buf, expected.len(), expected, outcome);
+}
+
+/// Implementation of [`read_assert!`](macro.read_assert.html), not public API.
+///
+/// Resources implementing `ReadTimeout` use the method of `&mut __Probe`,
+/// all others fall back to the method of `&mut &mut __Probe`,
+/// which only the arms without an explicit timeout use.
+#[doc(hidden)]
+pub struct __Probe<'a, R: ?Sized>(pub &'a mut R);
+
+#[doc(hidden)]
+pub trait __ReadAssertTimeout {
+    fn __read_assert(self, n: usize, expected: &[u8], timeout: Option<Duration>);
+}
+
+impl<'a, 'b, R> __ReadAssertTimeout for &'b mut __Probe<'a, R>
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    #[track_caller]
+    fn __read_assert(self, n: usize, expected: &[u8], timeout: Option<Duration>) {
+        let mut buf = Vec::new();
+        let outcome = read_full(self.0, &mut buf, n, timeout);
+
+        check("read_assert!", &buf, n, expected, outcome);
+    }
+}
+
+#[doc(hidden)]
+pub trait __ReadAssertBlocking {
+    fn __read_assert(self, n: usize, expected: &[u8], timeout: Option<Duration>);
+}
+
+impl<'a, 'b, 'c, R> __ReadAssertBlocking for &'c mut &'b mut __Probe<'a, R>
+where
+    R: Read + ?Sized,
+{
+    #[track_caller]
+    fn __read_assert(self, n: usize, expected: &[u8], _: Option<Duration>) {
+        let mut buf = Vec::new();
+        let outcome = read_blocking(self.0, &mut buf, n);
+
+        check("read_assert!", &buf, n, expected, outcome);
+    }
+}
+
+/// Reborrows `$resource` like a method call would, not public API.
+#[doc(hidden)]
+pub trait __ByRef {
+    #[inline]
+    fn __by_ref(&mut self) -> &mut Self {
+        self
+    }
+}
+
+impl<R: ?Sized> __ByRef for R {}
@@ -0,0 +1,520 @@
+use crate::diff::{escape, hex_diff};
+use crate::read::default_timeout;
+use crate::{try_channel, Error};
+
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::Builder;
+use std::time::{Duration, Instant};
+
+/// When [`Recorder::take()`] and [`assert_written!`] stop collecting bytes.
+///
+/// [`Recorder::take()`]: struct.Recorder.html#method.take
+/// [`assert_written!`]: macro.assert_written.html
+#[derive(Clone, Copy, Debug)]
+pub enum Until<'a> {
+    /// After the delimiter, which is included in the collected bytes.
+    Delimiter(&'a [u8]),
+
+    /// After the given number of bytes.
+    Bytes(usize),
+
+    /// When the writer shut down its write half or closed the stream.
+    Eof,
+
+    /// After the given duration, collecting everything written until then.
+    Timeout(Duration),
+}
+
+impl fmt::Display for Until<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Until::Delimiter(delimiter) => write!(f, "b\"{}\"", escape(delimiter)),
+            Until::Bytes(n) => write!(f, "{} bytes", n),
+            Until::Eof => f.write_str("EOF"),
+            Until::Timeout(timeout) => write!(f, "{:?} to pass", timeout),
+        }
+    }
+}
+
+/// Why collecting stopped.
+enum Stop {
+    Done,
+    Eof,
+    Error(String),
+    TimedOut(Duration),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Done => Ok(()),
+            Stop::Eof => f.write_str("reached EOF"),
+            Stop::Error(e) => write!(f, "reading failed with \"{}\"", e),
+            Stop::TimedOut(timeout) => write!(f, "timed out after {:?}", timeout),
+        }
+    }
+}
+
+/// Records everything arriving at a stream on a background thread.
+///
+/// This is useful for testing code which writes to a stream:
+/// the code under test gets one end of a channel,
+/// while the recorder reads the other end as fast as possible,
+/// so the code under test never blocks on a full buffer.
+/// The recorded bytes are then checked with [`assert_written!`] or [`take()`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_written, Recorder, Until};
+/// use std::io::Write;
+///
+/// let (mut stream, recorder) = Recorder::channel();
+///
+/// // the code under test
+/// stream.write_all(b"EHLO localhost\r\n").unwrap();
+/// stream.write_all(&[0; 100_000]).unwrap();
+/// drop(stream);
+///
+/// assert_written!(recorder, b"EHLO localhost\r\n", until = Until::Delimiter(b"\r\n"));
+/// assert_written!(recorder, vec![0; 100_000], until = Until::Eof);
+/// ```
+///
+/// [`assert_written!`]: macro.assert_written.html
+/// [`take()`]: #method.take
+pub struct Recorder {
+    shared: Arc<Shared>,
+    stream: Option<TcpStream>,
+}
+
+/// State shared with the recording thread.
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    /// Recorded bytes which were not taken yet
+    data: Vec<u8>,
+
+    /// How recording ended, if it did
+    end: Option<Stop>,
+}
+
+impl Recorder {
+    /// Starts recording everything read from `reader`.
+    ///
+    /// The background thread runs until `reader` reaches EOF or fails,
+    /// even if the recorder is dropped before.
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Recorder {
+        Recorder::start(reader, None)
+    }
+
+    /// Returns a TCP stream for the code under test and a recorder of its peer.
+    ///
+    /// The streams are created like in [`channel()`].
+    /// Dropping the recorder stops the background thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the streams cannot be created,
+    /// see [`try_channel()`] for a fallible version.
+    ///
+    /// [`channel()`]: fn.channel.html
+    /// [`try_channel()`]: #method.try_channel
+    #[inline]
+    pub fn channel() -> (TcpStream, Recorder) {
+        Recorder::try_channel().unwrap_or_else(|e| panic!("tcp-test: {}", e))
+    }
+
+    /// Returns a TCP stream for the code under test and a recorder of its peer, or an error.
+    pub fn try_channel() -> Result<(TcpStream, Recorder), Error> {
+        let (local, remote) = try_channel()?;
+        let stream = remote.try_clone().map_err(Error::Configure)?;
+
+        Ok((local, Recorder::start(remote, Some(stream))))
+    }
+
+    fn start<R: Read + Send + 'static>(reader: R, stream: Option<TcpStream>) -> Recorder {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                data: Vec::new(),
+                end: None,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let recording = shared.clone();
+
+        if let Err(e) = Builder::new()
+            .name(String::from("tcp-test recorder thread"))
+            .spawn(move || record(reader, recording))
+        {
+            shared.lock().end = Some(Stop::Error(e.to_string()));
+        }
+
+        Recorder { shared, stream }
+    }
+
+    /// Returns a copy of the recorded bytes which were not taken yet.
+    pub fn recorded(&self) -> Vec<u8> {
+        self.shared.lock().data.clone()
+    }
+
+    /// Waits until the condition is met and removes the collected bytes from the recording.
+    ///
+    /// Panics if the condition is not met within the [`default_timeout()`].
+    ///
+    /// [`default_timeout()`]: fn.default_timeout.html
+    #[track_caller]
+    pub fn take(&self, until: Until) -> Vec<u8> {
+        let (data, stop) = self.collect(until, default_timeout());
+
+        match stop {
+            Stop::Done => data,
+            stop => panic!(
+                "Recorder::take() {} waiting for {}, received {} bytes: b\"{}\"",
+                stop,
+                until,
+                data.len(),
+                escape(&data)
+            ),
+        }
+    }
+
+    /// Collects bytes until the condition is met, recording ends, or `timeout` passes.
+    fn collect(&self, until: Until, timeout: Option<Duration>) -> (Vec<u8>, Stop) {
+        let (deadline, timeout) = match until {
+            Until::Timeout(window) => (Some(Instant::now() + window), None),
+            _ => (timeout.map(|timeout| Instant::now() + timeout), timeout),
+        };
+
+        let mut state = self.shared.lock();
+
+        // bytes already searched for the delimiter, the data only grows while waiting
+        let mut searched: usize = 0;
+
+        loop {
+            let end = match until {
+                Until::Delimiter([]) => Some(0),
+                Until::Delimiter(delimiter) => {
+                    // the delimiter may begin in the bytes searched before
+                    let start = searched.saturating_sub(delimiter.len() - 1);
+                    searched = state.data.len();
+
+                    state.data[start..]
+                        .windows(delimiter.len())
+                        .position(|window| window == delimiter)
+                        .map(|position| start + position + delimiter.len())
+                }
+                Until::Bytes(n) if state.data.len() >= n => Some(n),
+                _ => None,
+            };
+
+            if let Some(end) = end {
+                return (state.data.drain(..end).collect(), Stop::Done);
+            }
+
+            let stop = match (&state.end, until) {
+                (Some(_), Until::Timeout(_)) => Some(Stop::Done),
+                (Some(Stop::Eof), Until::Eof) => Some(Stop::Done),
+                (Some(Stop::Eof), _) => Some(Stop::Eof),
+                (Some(Stop::Error(e)), _) => Some(Stop::Error(e.clone())),
+                _ => match deadline {
+                    Some(deadline) if Instant::now() >= deadline => match timeout {
+                        Some(timeout) => Some(Stop::TimedOut(timeout)),
+                        None => Some(Stop::Done),
+                    },
+                    _ => None,
+                },
+            };
+
+            if let Some(stop) = stop {
+                return (state.data.drain(..).collect(), stop);
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+
+                    self.shared
+                        .changed
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .shared
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("recorded", &self.shared.lock().data.len())
+            .finish()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Some(stream) = &self.stream {
+            // wakes up the recording thread
+            let _ = stream.shutdown(Shutdown::Read);
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Runs on the recording thread until `reader` reaches EOF or fails.
+fn record<R: Read>(mut reader: R, shared: Arc<Shared>) {
+    let mut buf = vec![0; 8192];
+
+    loop {
+        let result = reader.read(&mut buf);
+        let mut state = shared.lock();
+
+        match result {
+            Ok(0) => state.end = Some(Stop::Eof),
+            Ok(n) => state.data.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => state.end = Some(Stop::Error(e.to_string())),
+        }
+
+        shared.changed.notify_all();
+
+        if state.end.is_some() {
+            return;
+        }
+    }
+}
+
+/// Collects the bytes recorded by `recorder` until the condition is met
+/// and compares them with `expected`.
+///
+/// This is the function form of [`assert_written!`].
+/// Collecting stops after the [`default_timeout()`] unless the condition is a timeout itself.
+/// Panics with a hex diff if the bytes are not equal,
+/// or if the condition is not met.
+///
+/// [`assert_written!`]: macro.assert_written.html
+/// [`default_timeout()`]: fn.default_timeout.html
+#[track_caller]
+pub fn assert_written(recorder: &Recorder, expected: &[u8], until: Until) {
+    let name = "assert_written!";
+    let (data, stop) = recorder.collect(until, default_timeout());
+
+    if let Some(diff) = hex_diff(expected, &data) {
+        match stop {
+            Stop::Done => panic!("{} written bytes are not equal, {}", name, diff.trim_end()),
+            stop => panic!(
+                "{} written bytes are not equal, {} waiting for {}, {}",
+                name,
+                stop,
+                until,
+                diff.trim_end()
+            ),
+        }
+    }
+
+    if let Stop::Done = stop {
+        return;
+    }
+
+    panic!(
+        "{} received the expected bytes, but {} waiting for {}",
+        name, stop, until
+    );
+}
+
+/// Asserts that the bytes recorded by `$recorder` are `$expected`.
+///
+/// Collects the bytes written to the [`Recorder`] until the [`Until`] condition
+/// given as `until` is met, by default until as many bytes as expected were written.
+/// The collected bytes are removed from the recording,
+/// so consecutive writes can be checked one after another.
+/// Panics with a hex diff if the bytes are not equal,
+/// also see the function form [`assert_written()`].
+///
+/// # Example
+///
+/// ```should_panic
+/// use tcp_test::{assert_written, Recorder, Until};
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// let (mut stream, recorder) = Recorder::channel();
+///
+/// stream.write_all(b"PING").unwrap();
+/// assert_written!(recorder, b"PING");
+///
+/// stream.write_all(b"PING PING").unwrap();
+///
+/// // panics: nothing else may be written within 100ms
+/// assert_written!(recorder, b"PING", until = Until::Timeout(Duration::from_millis(100)));
+/// ```
+///
+/// [`Recorder`]: struct.Recorder.html
+/// [`Until`]: enum.Until.html
+/// [`assert_written()`]: fn.assert_written.html
+#[macro_export]
+macro_rules! assert_written {
+    ($recorder:expr, $expected:expr, until = $until:expr) => {{
+        match &$expected {
+            expected => $crate::assert_written(&$recorder, &expected[..], $until),
+        };
+    }};
+    ($recorder:expr, $expected:expr) => {{
+        match &$expected {
+            expected => $crate::assert_written(
+                &$recorder,
+                &expected[..],
+                $crate::Until::Bytes(expected[..].len()),
+            ),
+        };
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel;
+
+    use std::io::Write;
+
+    #[test]
+    fn written_bytes() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"abcdef").unwrap();
+
+        assert_written!(recorder, b"abc");
+        assert_written!(recorder, [b'd', b'e', b'f']);
+        assert!(recorder.recorded().is_empty());
+    }
+
+    #[test]
+    fn written_delimiter() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"a\r\nb\r\n").unwrap();
+
+        assert_eq!(recorder.take(Until::Delimiter(b"\r\n")), b"a\r\n");
+        assert_written!(recorder, b"b\r\n", until = Until::Delimiter(b"\r\n"));
+    }
+
+    #[test]
+    fn written_split_delimiter() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"ab\r").unwrap();
+
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            stream.write_all(b"\ncd").unwrap();
+            stream
+        });
+
+        assert_eq!(recorder.take(Until::Delimiter(b"\r\n")), b"ab\r\n");
+
+        drop(writer.join().unwrap());
+        assert_written!(recorder, b"cd", until = Until::Eof);
+    }
+
+    #[test]
+    fn written_empty_delimiter() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"rest").unwrap();
+
+        assert_written!(recorder, b"", until = Until::Delimiter(b""));
+        assert_written!(recorder, b"rest");
+    }
+
+    #[test]
+    fn written_eof() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"bye").unwrap();
+        drop(stream);
+
+        assert_written!(recorder, b"bye", until = Until::Eof);
+        assert_written!(recorder, b"", until = Until::Eof);
+    }
+
+    #[test]
+    fn written_timeout() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"tick").unwrap();
+
+        assert_written!(
+            recorder,
+            b"tick",
+            until = Until::Timeout(Duration::from_millis(50))
+        );
+    }
+
+    #[test]
+    fn recorder_new() {
+        let (mut local, remote) = channel();
+        let recorder = Recorder::new(remote);
+
+        local.write_all(b"new").unwrap();
+
+        assert_written!(recorder, b"new");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assert_written! written bytes are not equal, first difference at offset 2"
+    )]
+    fn written_diff() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"abd").unwrap();
+
+        assert_written!(recorder, b"abc");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "not equal, reached EOF waiting for 4 bytes, first difference at offset 2"
+    )]
+    fn written_short() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"ab").unwrap();
+        drop(stream);
+
+        assert_written!(recorder, b"abcd");
+    }
+
+    #[test]
+    fn collect_timed_out() {
+        let (mut stream, recorder) = Recorder::channel();
+
+        stream.write_all(b"ab").unwrap();
+
+        match recorder.collect(Until::Eof, Some(Duration::from_millis(100))) {
+            (data, Stop::TimedOut(timeout)) => {
+                assert_eq!(data, b"ab");
+                assert_eq!(timeout, Duration::from_millis(100));
+            }
+            (_, stop) => panic!("unexpected stop: {}", stop),
+        }
+    }
+}
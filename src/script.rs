@@ -0,0 +1,386 @@
+use crate::diff::escape;
+use crate::read::{default_timeout, mismatch, read_full, Outcome, ReadTimeout};
+use crate::{try_channel, Error};
+
+use std::error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread::{self, Builder, JoinHandle};
+use std::time::Duration;
+
+/// A conversation to hold with the peer of a stream, step by step.
+///
+/// Every `expect` step reads from the stream with the [`default_timeout()`]
+/// and compares the bytes like [`read_assert!`].
+/// The script stops at the first failing step.
+///
+/// # Example
+///
+/// Testing a client by scripting the server side:
+///
+/// ```
+/// use tcp_test::Script;
+/// use std::io::{Read, Write};
+///
+/// let (mut client, server) = Script::new()
+///     .expect(b"HELO\r\n")
+///     .send(b"250 OK\r\n")
+///     .expect_eof()
+///     .channel();
+///
+/// // the client under test
+/// client.write_all(b"HELO\r\n").unwrap();
+///
+/// let mut buf = [0; 8];
+/// client.read_exact(&mut buf).unwrap();
+/// assert_eq!(&buf, b"250 OK\r\n");
+/// drop(client);
+///
+/// server.join();
+/// ```
+///
+/// [`default_timeout()`]: fn.default_timeout.html
+/// [`read_assert!`]: macro.read_assert.html
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Expect(Vec<u8>),
+    Send(Vec<u8>),
+    ExpectEof,
+    Sleep(Duration),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        /// Bytes shown of an `expect()` or `send()` step.
+        const SHOWN: usize = 32;
+
+        let (name, bytes) = match self {
+            Step::Expect(bytes) => ("expect", bytes),
+            Step::Send(bytes) => ("send", bytes),
+            Step::ExpectEof => return f.write_str("expect_eof()"),
+            Step::Sleep(duration) => return write!(f, "sleep({:?})", duration),
+        };
+
+        if bytes.len() > SHOWN {
+            write!(f, "{}(b\"{}\"...)", name, escape(&bytes[..SHOWN]))
+        } else {
+            write!(f, "{}(b\"{}\")", name, escape(bytes))
+        }
+    }
+}
+
+impl Script {
+    /// Creates a new script without any steps.
+    #[inline]
+    pub fn new() -> Script {
+        Script::default()
+    }
+
+    /// Reads `bytes.len()` bytes and compares them with `bytes`.
+    #[inline]
+    pub fn expect(mut self, bytes: impl AsRef<[u8]>) -> Script {
+        self.steps.push(Step::Expect(bytes.as_ref().to_vec()));
+        self
+    }
+
+    /// Writes `bytes`.
+    #[inline]
+    pub fn send(mut self, bytes: impl AsRef<[u8]>) -> Script {
+        self.steps.push(Step::Send(bytes.as_ref().to_vec()));
+        self
+    }
+
+    /// Reads once and expects EOF.
+    #[inline]
+    pub fn expect_eof(mut self) -> Script {
+        self.steps.push(Step::ExpectEof);
+        self
+    }
+
+    /// Pauses for `duration`.
+    #[inline]
+    pub fn sleep(mut self, duration: Duration) -> Script {
+        self.steps.push(Step::Sleep(duration));
+        self
+    }
+
+    /// Runs the script against `stream` on the current thread.
+    ///
+    /// Returns which step failed and why.
+    pub fn run<S>(&self, stream: &mut S) -> Result<(), ScriptError>
+    where
+        S: Read + Write + ReadTimeout + ?Sized,
+    {
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Some(message) = self.step(stream, step) {
+                return Err(ScriptError {
+                    step: index + 1,
+                    steps: self.steps.len(),
+                    description: step.to_string(),
+                    message,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs a single step, returning what went wrong.
+    fn step<S>(&self, stream: &mut S, step: &Step) -> Option<String>
+    where
+        S: Read + Write + ReadTimeout + ?Sized,
+    {
+        match step {
+            Step::Expect(expected) => {
+                let mut buf = Vec::new();
+                let outcome = read_full(stream, &mut buf, expected.len(), default_timeout());
+
+                mismatch("reading", &buf, expected.len(), expected, outcome)
+            }
+            Step::Send(bytes) => stream
+                .write_all(bytes)
+                .and_then(|_| stream.flush())
+                .err()
+                .map(|e| format!("failed to send: {}", e)),
+            Step::ExpectEof => {
+                let mut buf = Vec::new();
+
+                match read_full(stream, &mut buf, 1, default_timeout()) {
+                    Outcome::Eof => None,
+                    Outcome::Complete => {
+                        Some(format!("expected EOF, but received b\"{}\"", escape(&buf)))
+                    }
+                    Outcome::TimedOut(timeout) => {
+                        Some(format!("timed out after {:?} waiting for EOF", timeout))
+                    }
+                    Outcome::Error(e) => Some(format!("expected EOF, but reading failed: {}", e)),
+                }
+            }
+            Step::Sleep(duration) => {
+                thread::sleep(*duration);
+                None
+            }
+        }
+    }
+
+    /// Runs the script against `stream` on a background thread.
+    ///
+    /// The stream is dropped, and thereby closed, after the last step.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread cannot be spawned,
+    /// see [`try_spawn()`] for a fallible version.
+    ///
+    /// [`try_spawn()`]: #method.try_spawn
+    #[inline]
+    pub fn spawn<S>(self, stream: S) -> ScriptHandle
+    where
+        S: Read + Write + ReadTimeout + Send + 'static,
+    {
+        self.try_spawn(stream)
+            .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+    }
+
+    /// Runs the script against `stream` on a background thread, or returns an error.
+    pub fn try_spawn<S>(self, mut stream: S) -> Result<ScriptHandle, Error>
+    where
+        S: Read + Write + ReadTimeout + Send + 'static,
+    {
+        let thread = Builder::new()
+            .name(String::from("tcp-test script thread"))
+            .spawn(move || self.run(&mut stream))
+            .map_err(Error::Thread)?;
+
+        Ok(ScriptHandle { thread })
+    }
+
+    /// Returns a TCP stream for the code under test,
+    /// while the script runs against its peer on a background thread.
+    ///
+    /// The streams are created like in [`channel()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the streams cannot be created or the thread cannot be spawned,
+    /// see [`try_channel()`] for a fallible version.
+    ///
+    /// [`channel()`]: fn.channel.html
+    /// [`try_channel()`]: #method.try_channel
+    #[inline]
+    pub fn channel(self) -> (TcpStream, ScriptHandle) {
+        self.try_channel()
+            .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+    }
+
+    /// Returns a TCP stream for the code under test and the running script, or an error.
+    pub fn try_channel(self) -> Result<(TcpStream, ScriptHandle), Error> {
+        let (local, remote) = try_channel()?;
+
+        Ok((local, self.try_spawn(remote)?))
+    }
+}
+
+/// A script running on a background thread.
+///
+/// Failures are only reported by [`join()`], so it should always be called.
+///
+/// [`join()`]: #method.join
+#[derive(Debug)]
+#[must_use = "failures of the script are only reported when joining"]
+pub struct ScriptHandle {
+    thread: JoinHandle<Result<(), ScriptError>>,
+}
+
+impl ScriptHandle {
+    /// Waits for the script to finish and panics if a step failed.
+    #[track_caller]
+    pub fn join(self) {
+        if let Err(e) = self.try_join() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Waits for the script to finish and returns which step failed.
+    pub fn try_join(self) -> Result<(), ScriptError> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// A failed step of a [`Script`].
+///
+/// [`Script`]: struct.Script.html
+#[derive(Debug)]
+pub struct ScriptError {
+    step: usize,
+    steps: usize,
+    description: String,
+    message: String,
+}
+
+impl ScriptError {
+    /// Returns the number of the failed step, starting at 1.
+    #[inline]
+    pub fn step(&self) -> usize {
+        self.step
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "script step {} of {}, {}, failed: {}",
+            self.step, self.steps, self.description, self.message
+        )
+    }
+}
+
+impl error::Error for ScriptError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{channel, read_assert};
+
+    #[test]
+    fn script_conversation() {
+        let (mut client, server) = Script::new()
+            .send(b"220 ready\r\n")
+            .expect(b"QUIT\r\n")
+            .send(b"221 bye\r\n")
+            .expect_eof()
+            .channel();
+
+        read_assert!(client, b"220 ready\r\n");
+        client.write_all(b"QUIT\r\n").unwrap();
+        read_assert!(client, b"221 bye\r\n");
+        drop(client);
+
+        server.join();
+    }
+
+    #[test]
+    fn script_closes() {
+        let (mut client, server) = Script::new().send(b"x").channel();
+
+        read_assert!(client, b"x");
+        crate::assert_eof!(client);
+
+        server.join();
+    }
+
+    #[test]
+    fn script_wrong_bytes() {
+        let (mut client, server) = Script::new()
+            .expect(b"HELO\r\n")
+            .expect(b"QUIT\r\n")
+            .channel();
+
+        client.write_all(b"HELO\r\nQUIT\n\r").unwrap();
+
+        let e = server.try_join().unwrap_err();
+        let message = e.to_string();
+
+        assert_eq!(e.step(), 2);
+        assert!(message.starts_with(
+            "script step 2 of 2, expect(b\"QUIT\\r\\n\"), failed: reading buffers are not equal, \
+             first difference at offset 4"
+        ));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "script step 1 of 2, expect_eof(), failed: expected EOF, but received b\"a\""
+    )]
+    fn script_unexpected_data() {
+        let (mut client, server) = Script::new().expect_eof().send(b"b").channel();
+
+        client.write_all(b"a").unwrap();
+
+        server.join();
+    }
+
+    #[test]
+    fn script_run() {
+        let (mut local, mut remote) = channel();
+
+        local.write_all(b"ping").unwrap();
+        drop(local);
+
+        let e = Script::new()
+            .expect(b"ping")
+            .expect(b"pong")
+            .run(&mut remote)
+            .unwrap_err();
+
+        assert_eq!(
+            e.to_string(),
+            "script step 2 of 2, expect(b\"pong\"), failed: \
+             reading reached EOF after receiving 0 of 4 bytes: []"
+        );
+    }
+
+    #[test]
+    fn step_display() {
+        let long = Step::Send(vec![b'a'; 40]);
+
+        assert_eq!(
+            long.to_string(),
+            format!("send(b\"{}\"...)", "a".repeat(32))
+        );
+        assert_eq!(
+            Step::Sleep(Duration::from_millis(5)).to_string(),
+            "sleep(5ms)"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::read::{default_timeout, read_full, Outcome, ReadTimeout};
+use crate::Transport;
+
+use std::io::{self, Read};
+use std::net::Shutdown;
+use std::time::Duration;
+
+/// Signals the end of data by shutting down the write half of `stream`.
+///
+/// The peer reads the remaining data followed by EOF,
+/// while `stream` can still read what the peer sends.
+/// Further writes to `stream` fail.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_eof, assert_write_fails, channel, half_close, read_assert};
+/// use std::io::Write;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// local.write_all(b"request").unwrap();
+/// half_close(&local).unwrap();
+///
+/// read_assert!(remote, 7, b"request");
+/// assert_eof!(remote);
+/// assert_write_fails!(local);
+///
+/// // the other direction is still open
+/// remote.write_all(b"response").unwrap();
+/// read_assert!(local, 8, b"response");
+/// ```
+#[inline]
+pub fn half_close(stream: &impl Transport) -> io::Result<()> {
+    stream.shutdown(Shutdown::Write)
+}
+
+/// Asserts that the next read from `$resource` returns EOF.
+///
+/// Panics with the received bytes if data arrives instead,
+/// or with the error if reading fails.
+/// Like [`read_assert!`], this waits for the [`default_timeout()`] at most,
+/// so `$resource` has to implement [`ReadTimeout`].
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_eof, channel};
+///
+/// let (local, mut remote) = channel();
+///
+/// drop(local);
+///
+/// assert_eof!(remote);
+/// ```
+///
+/// [`read_assert!`]: macro.read_assert.html
+/// [`default_timeout()`]: fn.default_timeout.html
+/// [`ReadTimeout`]: trait.ReadTimeout.html
+#[macro_export]
+macro_rules! assert_eof {
+    ($resource:expr) => {{
+        use $crate::__ByRef;
+
+        $crate::__assert_eof($resource.__by_ref());
+    }};
+}
+
+/// Implementation of [`assert_eof!`](macro.assert_eof.html), not public API.
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_eof<R>(reader: &mut R)
+where
+    R: Read + ReadTimeout + ?Sized,
+{
+    /// Bytes shown if data arrives instead.
+    const SHOWN: usize = 64;
+
+    let mut buf = Vec::new();
+
+    match read_full(reader, &mut buf, 1, default_timeout()) {
+        Outcome::Eof => {}
+        Outcome::Complete => {
+            // picks up what else already arrived to show it
+            let _ = read_full(reader, &mut buf, SHOWN, Some(Duration::from_secs(0)));
+
+            panic!(
+                "assert_eof! expected EOF, but received {} bytes: {:?}",
+                buf.len(),
+                buf
+            )
+        }
+        Outcome::TimedOut(timeout) => {
+            panic!("assert_eof! timed out after {:?} waiting for EOF", timeout)
+        }
+        Outcome::Error(e) => panic!("assert_eof! expected EOF, but reading failed: {}", e),
+    }
+}
+
+/// Asserts that writing to `$resource` fails, optionally with the given `std::io::ErrorKind`.
+///
+/// After the peer closed its read half, the first writes usually still succeed
+/// until the peer's reset arrives, so up to eight single byte writes are attempted
+/// with short pauses in between. Panics if all of them succeed.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{assert_write_fails, channel};
+/// use std::io::ErrorKind;
+/// use std::net::Shutdown;
+///
+/// let (mut local, mut remote) = channel();
+///
+/// local.shutdown(Shutdown::Write).unwrap();
+/// assert_write_fails!(local, ErrorKind::BrokenPipe);
+///
+/// drop(local);
+/// assert_write_fails!(remote);
+/// ```
+#[macro_export]
+macro_rules! assert_write_fails {
+    ($resource:expr) => {{
+        $crate::assert_write_fails!(@error $resource);
+    }};
+    ($resource:expr, $kind:expr) => {{
+        let error = $crate::assert_write_fails!(@error $resource);
+
+        assert_eq!(
+            error.kind(),
+            $kind,
+            "assert_write_fails! failed with an unexpected error: {}",
+            error
+        );
+    }};
+    (@error $resource:expr) => {{
+        use std::io::Write;
+        use $crate::__ByRef;
+
+        // evaluates `$resource` only once
+        match $resource.__by_ref() {
+            resource => {
+                let mut error = None;
+
+                for _ in 0..8 {
+                    match resource.write(&[0]).and_then(|_| resource.flush()) {
+                        Ok(()) => std::thread::sleep(std::time::Duration::from_millis(10)),
+                        Err(e) => {
+                            error = Some(e);
+                            break;
+                        }
+                    }
+                }
+
+                match error {
+                    Some(error) => error,
+                    None => panic!("assert_write_fails! writing succeeded 8 times"),
+                }
+            }
+        }
+    }};
+}
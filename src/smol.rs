@@ -0,0 +1,92 @@
+/*!
+Channels returning [smol] streams.
+
+Requires the `smol` feature.
+The streams are created on the blocking thread pool,
+so the executor is never blocked while waiting for the background thread.
+
+# Example
+
+```
+use tcp_test::smol::channel;
+use smol::io::{AsyncReadExt, AsyncWriteExt};
+
+# smol::block_on(async {
+let (mut local, mut remote) = channel().await;
+
+local.write_all(b"Hello, reader").await.unwrap();
+
+let mut buf = [0; 13];
+remote.read_exact(&mut buf).await.unwrap();
+
+assert_eq!(&buf, b"Hello, reader");
+# });
+```
+
+[smol]: https://github.com/smol-rs/smol
+*/
+
+use crate::{resolve, Error, DEFAULT_ADDRESS};
+
+use ::smol::net::TcpStream;
+
+use std::convert::TryFrom;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+
+/// Returns two smol TCP streams pointing at each other.
+///
+/// This is the asynchronous version of [`channel()`]
+/// and uses the same listener.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created.
+///
+/// [`channel()`]: ../fn.channel.html
+#[inline]
+pub async fn channel() -> (TcpStream, TcpStream) {
+    try_channel()
+        .await
+        .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two smol TCP streams pointing at each other.
+///
+/// This is the asynchronous version of [`channel_on()`]
+/// and uses the same listener.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created.
+///
+/// [`channel_on()`]: ../fn.channel_on.html
+#[inline]
+pub async fn channel_on(address: impl ToSocketAddrs) -> (TcpStream, TcpStream) {
+    try_channel_on(address)
+        .await
+        .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two smol TCP streams pointing at each other, or an error.
+#[inline]
+pub async fn try_channel() -> Result<(TcpStream, TcpStream), Error> {
+    pair(*DEFAULT_ADDRESS).await
+}
+
+/// Returns two smol TCP streams pointing at each other, or an error.
+#[inline]
+pub async fn try_channel_on(address: impl ToSocketAddrs) -> Result<(TcpStream, TcpStream), Error> {
+    pair(resolve(address)?).await
+}
+
+/// Creates a pair of std streams on the blocking thread pool and converts them.
+async fn pair(address: SocketAddr) -> Result<(TcpStream, TcpStream), Error> {
+    let (local, remote) = ::smol::unblock(move || crate::try_channel_on(address)).await?;
+
+    Ok((convert(local)?, convert(remote)?))
+}
+
+/// Converts a std stream into a smol stream.
+fn convert(stream: net::TcpStream) -> Result<TcpStream, Error> {
+    TcpStream::try_from(stream).map_err(Error::Configure)
+}
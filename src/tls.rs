@@ -0,0 +1,339 @@
+/*!
+TLS channels using [rustls].
+
+Requires the `tls` feature.
+A certificate authority and a server certificate valid for `localhost`,
+`127.0.0.1` and `::1` are generated once per program run.
+The [`TlsBuilder`] allows supplying other certificates and ALPN protocols.
+
+# Example
+
+```
+use tcp_test::tls::channel;
+use std::io::{Read, Write};
+
+let (mut client, mut server) = channel();
+
+client.write_all(b"Hello, server").unwrap();
+client.flush().unwrap();
+
+let mut buf = [0; 13];
+server.read_exact(&mut buf).unwrap();
+
+assert_eq!(&buf, b"Hello, server");
+```
+
+[rustls]: https://docs.rs/rustls
+[`TlsBuilder`]: struct.TlsBuilder.html
+*/
+
+pub use ::rustls;
+
+use crate::{resolve, Error, DEFAULT_ADDRESS};
+
+use ::rustls::crypto::{ring, CryptoProvider};
+use ::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+use ::rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection};
+use ::rustls::{ConnectionCommon, SideData, StreamOwned};
+use lazy_static::lazy_static;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+
+use std::convert::TryFrom;
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::thread;
+
+/// The client end of a TLS channel.
+pub type ClientStream = StreamOwned<ClientConnection, TcpStream>;
+
+/// The server end of a TLS channel.
+pub type ServerStream = StreamOwned<ServerConnection, TcpStream>;
+
+lazy_static! {
+    static ref CERTIFICATES: Certificates =
+        Certificates::generate().expect("failed to generate the tcp-test certificates");
+}
+
+/// The generated certificate authority and server certificate.
+struct Certificates {
+    ca: CertificateDer<'static>,
+    server: CertificateDer<'static>,
+    key: PrivatePkcs8KeyDer<'static>,
+}
+
+impl Certificates {
+    fn generate() -> Result<Certificates, rcgen::Error> {
+        let ca_key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(Vec::new())?;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "tcp-test certificate authority");
+        let ca = params.self_signed(&ca_key)?;
+
+        let key = KeyPair::generate()?;
+        let params = CertificateParams::new(vec![
+            String::from("localhost"),
+            String::from("127.0.0.1"),
+            String::from("::1"),
+        ])?;
+        let server = params.signed_by(&key, &ca, &ca_key)?;
+
+        Ok(Certificates {
+            ca: ca.der().clone(),
+            server: server.der().clone(),
+            key: PrivatePkcs8KeyDer::from(key.serialize_der()),
+        })
+    }
+}
+
+/// Returns the generated certificate authority,
+/// which signed the default server certificate.
+///
+/// This is useful for configuring clients which are not created by this module.
+pub fn ca_certificate() -> CertificateDer<'static> {
+    CERTIFICATES.ca.clone()
+}
+
+/// Returns a TLS client and server stream pointing at each other,
+/// with the handshake already completed.
+///
+/// The underlying TCP streams are created like in [`channel()`].
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created or the handshake fails,
+/// see [`try_channel()`] for a fallible version.
+///
+/// [`channel()`]: ../fn.channel.html
+/// [`try_channel()`]: fn.try_channel.html
+#[inline]
+pub fn channel() -> (ClientStream, ServerStream) {
+    try_channel().unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns a TLS client and server stream pointing at each other, or an error.
+#[inline]
+pub fn try_channel() -> Result<(ClientStream, ServerStream), Error> {
+    TlsBuilder::new().build()
+}
+
+/// Creates TLS channels with custom certificates or ALPN protocols.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::tls::TlsBuilder;
+///
+/// let (client, server) = TlsBuilder::new()
+///     .alpn(vec![b"h2".to_vec(), b"http/1.1".to_vec()])
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(client.conn.alpn_protocol(), Some(&b"h2"[..]));
+/// assert_eq!(server.conn.alpn_protocol(), Some(&b"h2"[..]));
+/// ```
+#[derive(Debug)]
+pub struct TlsBuilder {
+    certificate: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    roots: Option<RootCertStore>,
+    server_name: String,
+    alpn: Vec<Vec<u8>>,
+    client_config: Option<Arc<ClientConfig>>,
+    server_config: Option<Arc<ServerConfig>>,
+}
+
+impl Default for TlsBuilder {
+    fn default() -> TlsBuilder {
+        TlsBuilder {
+            certificate: None,
+            roots: None,
+            server_name: String::from("localhost"),
+            alpn: Vec::new(),
+            client_config: None,
+            server_config: None,
+        }
+    }
+}
+
+impl TlsBuilder {
+    /// Creates a new builder using the generated certificates.
+    #[inline]
+    pub fn new() -> TlsBuilder {
+        TlsBuilder::default()
+    }
+
+    /// Sets the certificate chain and private key of the server.
+    ///
+    /// The client still only trusts the generated certificate authority,
+    /// use [`root_certificates()`] to change that.
+    ///
+    /// [`root_certificates()`]: #method.root_certificates
+    #[inline]
+    pub fn certificate(
+        mut self,
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> TlsBuilder {
+        self.certificate = Some((chain, key));
+        self
+    }
+
+    /// Sets the certificate authorities trusted by the client.
+    #[inline]
+    pub fn root_certificates(mut self, roots: RootCertStore) -> TlsBuilder {
+        self.roots = Some(roots);
+        self
+    }
+
+    /// Sets the server name the client verifies, `localhost` by default.
+    #[inline]
+    pub fn server_name(mut self, name: impl Into<String>) -> TlsBuilder {
+        self.server_name = name.into();
+        self
+    }
+
+    /// Sets the ALPN protocols offered by the client and supported by the server,
+    /// in order of preference.
+    #[inline]
+    pub fn alpn(mut self, protocols: Vec<Vec<u8>>) -> TlsBuilder {
+        self.alpn = protocols;
+        self
+    }
+
+    /// Uses `config` for the client, ignoring the root certificates and ALPN protocols.
+    #[inline]
+    pub fn client_config(mut self, config: Arc<ClientConfig>) -> TlsBuilder {
+        self.client_config = Some(config);
+        self
+    }
+
+    /// Uses `config` for the server, ignoring the certificate and ALPN protocols.
+    #[inline]
+    pub fn server_config(mut self, config: Arc<ServerConfig>) -> TlsBuilder {
+        self.server_config = Some(config);
+        self
+    }
+
+    /// Returns a TLS client and server stream pointing at each other,
+    /// using the same listener as [`channel()`].
+    ///
+    /// [`channel()`]: ../fn.channel.html
+    #[inline]
+    pub fn build(self) -> Result<(ClientStream, ServerStream), Error> {
+        self.connect(*DEFAULT_ADDRESS)
+    }
+
+    /// Returns a TLS client and server stream pointing at each other,
+    /// using the same listener as [`channel_on()`].
+    ///
+    /// [`channel_on()`]: ../fn.channel_on.html
+    #[inline]
+    pub fn build_on(
+        self,
+        address: impl ToSocketAddrs,
+    ) -> Result<(ClientStream, ServerStream), Error> {
+        let address = resolve(address)?;
+
+        self.connect(address)
+    }
+
+    fn connect(self, address: SocketAddr) -> Result<(ClientStream, ServerStream), Error> {
+        let server_name = ServerName::try_from(self.server_name.clone()).map_err(tls_error)?;
+        let client_config = self.make_client_config()?;
+        let server_config = self.make_server_config()?;
+
+        let client = ClientConnection::new(client_config, server_name).map_err(tls_error)?;
+        let server = ServerConnection::new(server_config).map_err(tls_error)?;
+
+        let (local, remote) = crate::try_channel_on(address)?;
+
+        let server = thread::Builder::new()
+            .name(String::from("tcp-test TLS handshake"))
+            .spawn(move || handshake(server, remote))
+            .map_err(Error::Thread)?;
+
+        let client = handshake(client, local);
+        let server = server
+            .join()
+            .map_err(|_| Error::Thread(io::Error::other("the TLS handshake thread panicked")))?;
+
+        Ok((client?, server?))
+    }
+
+    fn make_client_config(&self) -> Result<Arc<ClientConfig>, Error> {
+        if let Some(config) = &self.client_config {
+            return Ok(config.clone());
+        }
+
+        let roots = match &self.roots {
+            Some(roots) => roots.clone(),
+            None => {
+                let mut roots = RootCertStore::empty();
+                roots.add(ca_certificate()).map_err(tls_error)?;
+                roots
+            }
+        };
+
+        let mut config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = self.alpn.clone();
+
+        Ok(Arc::new(config))
+    }
+
+    fn make_server_config(&self) -> Result<Arc<ServerConfig>, Error> {
+        if let Some(config) = &self.server_config {
+            return Ok(config.clone());
+        }
+
+        let (chain, key) = match &self.certificate {
+            Some((chain, key)) => (chain.clone(), key.clone_key()),
+            None => (
+                vec![CERTIFICATES.server.clone()],
+                PrivateKeyDer::Pkcs8(CERTIFICATES.key.clone_key()),
+            ),
+        };
+
+        let mut config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .map_err(tls_error)?;
+        config.alpn_protocols = self.alpn.clone();
+
+        Ok(Arc::new(config))
+    }
+}
+
+/// Drives the handshake of `connection` over `stream` to completion.
+fn handshake<C, S>(
+    mut connection: C,
+    mut stream: TcpStream,
+) -> Result<StreamOwned<C, TcpStream>, Error>
+where
+    C: DerefMut<Target = ConnectionCommon<S>>,
+    S: SideData,
+{
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream).map_err(Error::Tls)?;
+    }
+
+    Ok(StreamOwned::new(connection, stream))
+}
+
+/// Returns the `ring` crypto provider, independent of the process-wide default.
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// Converts a configuration error.
+fn tls_error(e: impl std::error::Error + Send + Sync + 'static) -> Error {
+    Error::Tls(io::Error::new(io::ErrorKind::InvalidInput, e))
+}
@@ -0,0 +1,106 @@
+/*!
+Channels returning [Tokio] streams.
+
+Requires the `tokio` feature.
+The streams are created on the blocking thread pool,
+so the runtime is never blocked while waiting for the background thread.
+
+# Example
+
+```
+use tcp_test::tokio::channel;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+# #[tokio::main(flavor = "current_thread")]
+# async fn main() {
+let (mut local, mut remote) = channel().await;
+
+local.write_all(b"Hello, reader").await.unwrap();
+
+let mut buf = [0; 13];
+remote.read_exact(&mut buf).await.unwrap();
+
+assert_eq!(&buf, b"Hello, reader");
+# }
+```
+
+[Tokio]: https://tokio.rs
+*/
+
+use crate::{resolve, Error, DEFAULT_ADDRESS};
+
+use ::tokio::net::TcpStream;
+use ::tokio::task;
+
+use std::io;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+
+/// Returns two Tokio TCP streams pointing at each other.
+///
+/// This is the asynchronous version of [`channel()`]
+/// and uses the same listener.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created, or if called outside of a Tokio runtime.
+///
+/// [`channel()`]: ../fn.channel.html
+#[inline]
+pub async fn channel() -> (TcpStream, TcpStream) {
+    try_channel()
+        .await
+        .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two Tokio TCP streams pointing at each other.
+///
+/// This is the asynchronous version of [`channel_on()`]
+/// and uses the same listener.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created, or if called outside of a Tokio runtime.
+///
+/// [`channel_on()`]: ../fn.channel_on.html
+#[inline]
+pub async fn channel_on(address: impl ToSocketAddrs) -> (TcpStream, TcpStream) {
+    try_channel_on(address)
+        .await
+        .unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two Tokio TCP streams pointing at each other, or an error.
+///
+/// # Panics
+///
+/// Panics if called outside of a Tokio runtime.
+#[inline]
+pub async fn try_channel() -> Result<(TcpStream, TcpStream), Error> {
+    pair(*DEFAULT_ADDRESS).await
+}
+
+/// Returns two Tokio TCP streams pointing at each other, or an error.
+///
+/// # Panics
+///
+/// Panics if called outside of a Tokio runtime.
+#[inline]
+pub async fn try_channel_on(address: impl ToSocketAddrs) -> Result<(TcpStream, TcpStream), Error> {
+    pair(resolve(address)?).await
+}
+
+/// Creates a pair of std streams on the blocking thread pool and converts them.
+async fn pair(address: SocketAddr) -> Result<(TcpStream, TcpStream), Error> {
+    let (local, remote) = task::spawn_blocking(move || crate::try_channel_on(address))
+        .await
+        .map_err(|e| Error::Thread(io::Error::other(e)))??;
+
+    Ok((convert(local)?, convert(remote)?))
+}
+
+/// Converts a std stream into a Tokio stream.
+fn convert(stream: net::TcpStream) -> Result<TcpStream, Error> {
+    stream.set_nonblocking(true).map_err(Error::Configure)?;
+
+    TcpStream::from_std(stream).map_err(Error::Configure)
+}
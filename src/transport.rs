@@ -0,0 +1,75 @@
+use crate::Error;
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+/// A stream type for which connected pairs can be created.
+///
+/// This allows writing tests generic over the transport,
+/// running the same test against both TCP and Unix domain sockets.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::Transport;
+/// use std::io::{Read, Write};
+///
+/// fn echo<T: Transport>() {
+///     let (mut local, mut remote) = T::channel();
+///
+///     local.write_all(b"ping").unwrap();
+///
+///     let mut buf = [0; 4];
+///     remote.read_exact(&mut buf).unwrap();
+///
+///     assert_eq!(&buf, b"ping");
+/// }
+///
+/// echo::<std::net::TcpStream>();
+/// # #[cfg(unix)]
+/// echo::<std::os::unix::net::UnixStream>();
+/// ```
+pub trait Transport: Read + Write + Send + Sized + 'static {
+    /// Returns two streams pointing at each other, or an error.
+    fn try_channel() -> Result<(Self, Self), Error>;
+
+    /// Returns two streams pointing at each other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the streams cannot be created.
+    #[inline]
+    fn channel() -> (Self, Self) {
+        Self::try_channel().unwrap_or_else(|e| panic!("tcp-test: {}", e))
+    }
+
+    /// Shuts down the read, write, or both halves of the stream.
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+}
+
+impl Transport for TcpStream {
+    #[inline]
+    fn try_channel() -> Result<(Self, Self), Error> {
+        crate::try_channel()
+    }
+
+    #[inline]
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixStream {
+    #[inline]
+    fn try_channel() -> Result<(Self, Self), Error> {
+        crate::unix::try_channel()
+    }
+
+    #[inline]
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        UnixStream::shutdown(self, how)
+    }
+}
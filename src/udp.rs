@@ -0,0 +1,176 @@
+/*!
+UDP socket pairs for testing datagram protocols.
+
+# Example
+
+```
+use tcp_test::{recv_assert, udp::channel};
+
+let (local, remote) = channel();
+
+local.send(b"Hello, receiver").unwrap();
+
+recv_assert!(remote, b"Hello, receiver");
+```
+*/
+
+use crate::{__assert_bytes_eq, Error, DEFAULT_ADDRESS};
+
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+/// Returns two UDP sockets connected to each other.
+///
+/// Both sockets are bound to `127.0.0.1` on a port chosen by the operating system,
+/// so `send()` and `recv()` can be used directly.
+///
+/// # Panics
+///
+/// Panics if the sockets cannot be created,
+/// see [`try_channel()`] for a fallible version.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::udp::channel;
+///
+/// let (local, remote) = channel();
+///
+/// assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+/// assert_eq!(local.peer_addr().unwrap(), remote.local_addr().unwrap());
+/// ```
+///
+/// [`try_channel()`]: fn.try_channel.html
+#[inline]
+pub fn channel() -> (UdpSocket, UdpSocket) {
+    try_channel().unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two UDP sockets connected to each other, or an error.
+pub fn try_channel() -> Result<(UdpSocket, UdpSocket), Error> {
+    let local = UdpSocket::bind(*DEFAULT_ADDRESS).map_err(Error::Bind)?;
+    let remote = UdpSocket::bind(*DEFAULT_ADDRESS).map_err(Error::Bind)?;
+
+    let local_addr = local.local_addr().map_err(Error::Bind)?;
+    let remote_addr = remote.local_addr().map_err(Error::Bind)?;
+
+    local.connect(remote_addr).map_err(Error::Connect)?;
+    remote.connect(local_addr).map_err(Error::Connect)?;
+
+    Ok((local, remote))
+}
+
+/// Convenience macro for receiving and comparing a single datagram.
+///
+/// Receives the next datagram from the connected UDP socket `$socket`
+/// and compares its contents with `$expected`.
+/// Panics if the datagram differs, including if it is longer or shorter.
+///
+/// Receiving fails after the [`default_timeout()`] or the `timeout` given as last argument,
+/// which is either a `Duration` or an `Option<Duration>`.
+/// The read timeout of the socket is restored afterwards.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::{recv_assert, udp::channel};
+/// use std::time::Duration;
+///
+/// let (local, remote) = channel();
+///
+/// local.send(&[1, 2, 3]).unwrap();
+/// local.send(&[4]).unwrap();
+///
+/// recv_assert!(remote, [1, 2, 3]);
+/// recv_assert!(remote, [4], timeout = Duration::from_millis(100));
+/// ```
+///
+/// [`default_timeout()`]: ../fn.default_timeout.html
+#[macro_export]
+macro_rules! recv_assert {
+    ($socket:expr, $expected:expr, timeout = $timeout:expr) => {{
+        match &$expected {
+            expected => {
+                $crate::udp::__recv_assert(&$socket, &expected[..], $timeout.into());
+            }
+        };
+    }};
+    ($socket:expr, $expected:expr) => {{
+        $crate::recv_assert!($socket, $expected, timeout = $crate::default_timeout());
+    }};
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __recv_assert(socket: &UdpSocket, expected: &[u8], timeout: Option<Duration>) {
+    let previous = socket
+        .read_timeout()
+        .unwrap_or_else(|e| panic!("failed to receive in recv_assert!: {}", e));
+
+    // the operating system rejects a zero timeout, so wait at least a moment
+    socket
+        .set_read_timeout(timeout.map(|timeout| timeout.max(Duration::from_millis(1))))
+        .unwrap_or_else(|e| panic!("failed to receive in recv_assert!: {}", e));
+
+    // the maximum size of a UDP datagram
+    let mut buf = vec![0; 65536];
+    let result = socket.recv(&mut buf);
+
+    let _ = socket.set_read_timeout(previous);
+
+    match result {
+        Ok(n) => __assert_bytes_eq("recv_assert! datagrams", &buf[..n], expected),
+        Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+            panic!(
+                "recv_assert! received no datagram within {:?}",
+                timeout.unwrap_or_default()
+            )
+        }
+        Err(e) => panic!("failed to receive in recv_assert!: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recv_assert_ok() {
+        let (local, remote) = channel();
+
+        local.send(b"datagram").unwrap();
+        remote.send(b"reply").unwrap();
+
+        recv_assert!(remote, b"datagram");
+        recv_assert!(local, b"reply");
+    }
+
+    #[test]
+    #[should_panic]
+    fn recv_assert_panic() {
+        let (local, remote) = channel();
+
+        local.send(b"datagram, longer").unwrap();
+
+        recv_assert!(remote, b"datagram");
+    }
+
+    #[test]
+    #[should_panic(expected = "recv_assert! received no datagram within 50ms")]
+    fn recv_assert_timeout() {
+        let (_local, remote) = channel();
+
+        recv_assert!(remote, b"datagram", timeout = Duration::from_millis(50));
+    }
+
+    #[test]
+    fn recv_assert_restores_timeout() {
+        let (local, remote) = channel();
+
+        local.send(b"datagram").unwrap();
+        recv_assert!(remote, b"datagram", timeout = Duration::from_millis(50));
+
+        assert_eq!(remote.read_timeout().unwrap(), None);
+    }
+}
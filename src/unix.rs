@@ -0,0 +1,101 @@
+/*!
+Unix domain socket pairs with the same API as [`channel()`].
+
+Only available on Unix platforms.
+
+# Example
+
+```
+use tcp_test::unix::channel;
+use std::io::{Read, Write};
+
+let (mut local, mut remote) = channel();
+
+local.write_all(b"Hello, reader").unwrap();
+
+let mut buf = [0; 13];
+remote.read_exact(&mut buf).unwrap();
+
+assert_eq!(&buf, b"Hello, reader");
+```
+
+[`channel()`]: ../fn.channel.html
+*/
+
+use crate::Error;
+
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+
+/// Returns two Unix streams pointing at each other.
+///
+/// The streams are created with `socketpair()`, so no file system path is used.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created,
+/// see [`try_channel()`] for a fallible version.
+///
+/// [`try_channel()`]: fn.try_channel.html
+#[inline]
+pub fn channel() -> (UnixStream, UnixStream) {
+    try_channel().unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two Unix streams pointing at each other,
+/// using a listener bound to `path`.
+///
+/// Unlike [`channel()`], the second stream is accepted by a `UnixListener`,
+/// so both streams have a socket address. The socket file at `path`
+/// is removed again once the streams are connected.
+///
+/// # Panics
+///
+/// Panics if the streams cannot be created,
+/// see [`try_channel_on()`] for a fallible version.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::unix::channel_on;
+///
+/// let path = std::env::temp_dir().join("tcp-test-doc.sock");
+/// let (local, remote) = channel_on(&path);
+///
+/// assert_eq!(local.peer_addr().unwrap().as_pathname(), Some(path.as_ref()));
+/// assert_eq!(remote.local_addr().unwrap().as_pathname(), Some(path.as_ref()));
+/// ```
+///
+/// [`channel()`]: fn.channel.html
+/// [`try_channel_on()`]: fn.try_channel_on.html
+#[inline]
+pub fn channel_on(path: impl AsRef<Path>) -> (UnixStream, UnixStream) {
+    try_channel_on(path).unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Returns two Unix streams pointing at each other, or an error.
+#[inline]
+pub fn try_channel() -> Result<(UnixStream, UnixStream), Error> {
+    UnixStream::pair().map_err(Error::Connect)
+}
+
+/// Returns two Unix streams pointing at each other,
+/// using a listener bound to `path`, or an error.
+pub fn try_channel_on(path: impl AsRef<Path>) -> Result<(UnixStream, UnixStream), Error> {
+    let path = path.as_ref();
+
+    let listener = UnixListener::bind(path).map_err(Error::Bind)?;
+
+    let result = UnixStream::connect(path)
+        .map_err(Error::Connect)
+        .and_then(|local| {
+            let (remote, _) = listener.accept().map_err(Error::Accept)?;
+
+            Ok((local, remote))
+        });
+
+    // the connected streams stay usable without the socket file
+    let _ = std::fs::remove_file(path);
+
+    result
+}
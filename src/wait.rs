@@ -0,0 +1,230 @@
+use crate::client::connect_retry;
+use crate::{resolve, Error};
+
+use std::io;
+use std::net::ToSocketAddrs;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+/// Waits until a server is listening on `address`.
+///
+/// Connects with increasing pauses between the attempts until a connection succeeds,
+/// which is closed again right away, so the server sees one additional connection.
+/// Unspecified addresses like `0.0.0.0` are connected to using the loopback address.
+///
+/// # Panics
+///
+/// Panics if nothing is listening on `address` within `timeout`,
+/// see [`try_wait_for_listening()`] for a fallible version.
+///
+/// # Example
+///
+/// ```
+/// use tcp_test::wait_for_listening;
+/// use std::net::TcpListener;
+/// use std::time::Duration;
+///
+/// // a free port, the listener is dropped right away
+/// let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+///
+/// // the server under test, starting slowly
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_millis(50));
+///     let listener = TcpListener::bind(address).unwrap();
+///     for _ in listener.incoming() {}
+/// });
+///
+/// wait_for_listening(address, Duration::from_secs(5));
+/// ```
+///
+/// [`try_wait_for_listening()`]: fn.try_wait_for_listening.html
+#[inline]
+pub fn wait_for_listening(address: impl ToSocketAddrs, timeout: Duration) {
+    try_wait_for_listening(address, timeout).unwrap_or_else(|e| panic!("tcp-test: {}", e));
+}
+
+/// Waits until a server is listening on `address`, or returns an error after `timeout`.
+pub fn try_wait_for_listening(address: impl ToSocketAddrs, timeout: Duration) -> Result<(), Error> {
+    let address = resolve(address)?;
+
+    connect_retry(address, Some(timeout), || Ok(())).map(drop)
+}
+
+/// Spawns the server process of `command` and waits until it is listening on `address`.
+///
+/// Works like [`wait_for_listening()`], but fails early if the process exits.
+/// The process is killed if waiting fails,
+/// otherwise the caller is responsible for killing it.
+///
+/// # Panics
+///
+/// Panics if the process cannot be spawned, exits,
+/// or does not listen on `address` within `timeout`,
+/// see [`try_spawn_listening()`] for a fallible version.
+///
+/// # Example
+///
+/// ```no_run
+/// use tcp_test::{spawn_listening, TestClient};
+/// use std::process::Command;
+/// use std::time::Duration;
+///
+/// let mut server = spawn_listening(
+///     Command::new("target/debug/my-server").arg("--port=8080"),
+///     "127.0.0.1:8080",
+///     Duration::from_secs(10),
+/// );
+///
+/// let client = TestClient::connect("127.0.0.1:8080");
+/// // ...
+///
+/// server.kill().unwrap();
+/// ```
+///
+/// [`wait_for_listening()`]: fn.wait_for_listening.html
+/// [`try_spawn_listening()`]: fn.try_spawn_listening.html
+#[inline]
+pub fn spawn_listening(
+    command: &mut Command,
+    address: impl ToSocketAddrs,
+    timeout: Duration,
+) -> Child {
+    try_spawn_listening(command, address, timeout).unwrap_or_else(|e| panic!("tcp-test: {}", e))
+}
+
+/// Spawns the server process of `command` and waits until it is listening on `address`,
+/// or returns an error.
+pub fn try_spawn_listening(
+    command: &mut Command,
+    address: impl ToSocketAddrs,
+    timeout: Duration,
+) -> Result<Child, Error> {
+    let address = resolve(address)?;
+    let mut child = command.spawn().map_err(Error::Spawn)?;
+
+    let result = connect_retry(address, Some(timeout), || match child.try_wait() {
+        Ok(None) => Ok(()),
+        Ok(Some(status)) => Err(Error::Spawn(io::Error::other(format!(
+            "the process exited with {} before listening on {}",
+            status, address
+        )))),
+        Err(e) => Err(Error::Spawn(e)),
+    });
+
+    match result {
+        Ok(_) => Ok(child),
+        Err(e) => {
+            let _ = child.kill();
+            let _ = child.wait();
+
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unused_address;
+
+    use std::env;
+    use std::io::ErrorKind;
+    use std::net::{SocketAddr, TcpListener};
+    use std::process::Stdio;
+    use std::thread;
+    use std::time::Instant;
+
+    /// Set for the child process started by `spawn_child_server`.
+    const CHILD_ADDRESS: &str = "TCP_TEST_CHILD_ADDRESS";
+
+    /// Runs this test binary as a child process, executing only `child_server`.
+    fn child_command(address: SocketAddr) -> Command {
+        let mut command = Command::new(env::current_exe().unwrap());
+
+        command
+            .args(["wait::tests::child_server", "--exact", "--ignored"])
+            .env(CHILD_ADDRESS, address.to_string())
+            .stdout(Stdio::null());
+
+        command
+    }
+
+    #[test]
+    #[ignore = "started as a child process by spawn_child_server"]
+    fn child_server() {
+        let address = match env::var(CHILD_ADDRESS) {
+            Ok(address) => address,
+            Err(_) => return,
+        };
+
+        thread::sleep(Duration::from_millis(100));
+
+        let listener = TcpListener::bind(address).unwrap();
+        for _ in listener.incoming() {}
+    }
+
+    #[test]
+    fn wait_late_listener() {
+        let address = unused_address();
+
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+
+            TcpListener::bind(address).unwrap().accept().unwrap();
+        });
+
+        wait_for_listening(address, Duration::from_secs(5));
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let start = Instant::now();
+
+        match try_wait_for_listening(unused_address(), Duration::from_millis(100)) {
+            Err(Error::Connect(e)) => assert_eq!(e.kind(), ErrorKind::TimedOut),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn spawn_child_server() {
+        let address = unused_address();
+
+        let mut child = spawn_listening(
+            &mut child_command(address),
+            address,
+            Duration::from_secs(10),
+        );
+
+        assert!(child.try_wait().unwrap().is_none());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn spawn_exits() {
+        let address = unused_address();
+        let mut command = child_command(address);
+        command.env_remove(CHILD_ADDRESS);
+
+        match try_spawn_listening(&mut command, address, Duration::from_secs(10)) {
+            Err(Error::Spawn(e)) => assert!(e.to_string().contains("exited with")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn spawn_missing() {
+        let mut command = Command::new("tcp-test-this-does-not-exist");
+
+        match try_spawn_listening(&mut command, unused_address(), Duration::from_secs(1)) {
+            Err(Error::Spawn(e)) => assert_eq!(e.kind(), ErrorKind::NotFound),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
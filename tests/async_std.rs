@@ -0,0 +1,47 @@
+#![cfg(feature = "async-std")]
+
+use async_std::io::{ReadExt, WriteExt};
+use tcp_test::async_std::{channel, channel_on, try_channel};
+use tcp_test::listener_addr;
+
+mod common;
+
+macro_rules! send_read {
+    ($local:expr, $remote:expr, $data:expr) => {
+        let (mut local, mut remote) = ($local, $remote);
+
+        local.write_all($data).await.unwrap();
+
+        let mut buf = [0; 8];
+        remote.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf, $data);
+    };
+}
+
+#[async_std::test]
+async fn channel_0() {
+    let (local, remote) = channel().await;
+
+    assert_eq!(local.peer_addr().unwrap(), listener_addr());
+    assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+
+    send_read!(local, remote, b"a5yNc-0s");
+}
+
+#[async_std::test]
+async fn channel_1() {
+    let (local, remote) = try_channel().await.unwrap();
+
+    send_read!(remote, local, b"s0-cNy5a");
+}
+
+#[async_std::test]
+async fn channel_on_0() {
+    let address = common::unused_address("127.0.0.1");
+    let (local, remote) = channel_on(address).await;
+
+    assert_eq!(local.peer_addr().unwrap(), address);
+
+    send_read!(local, remote, b"a5;0N_31");
+}
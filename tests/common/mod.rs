@@ -0,0 +1,6 @@
+use std::net::{SocketAddr, TcpListener};
+
+/// Returns an address on `host` which most likely nobody listens on.
+pub fn unused_address(host: &str) -> SocketAddr {
+    TcpListener::bind((host, 0)).unwrap().local_addr().unwrap()
+}
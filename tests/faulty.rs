@@ -0,0 +1,134 @@
+use std::io::{ErrorKind, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+use tcp_test::{abort, read_assert, FaultyChannel};
+
+#[test]
+fn no_faults() {
+    let (mut local, mut remote) = FaultyChannel::new().build().unwrap();
+
+    local.write_all(b"f4u1ty#0").unwrap();
+    read_assert!(remote, 8, b"f4u1ty#0");
+
+    remote.write_all(b"0#yt1u4f").unwrap();
+    read_assert!(local, 8, b"0#yt1u4f");
+}
+
+#[test]
+fn shutdown() {
+    let (mut local, mut remote) = FaultyChannel::new().build().unwrap();
+
+    local.write_all(b"last").unwrap();
+    local.shutdown(std::net::Shutdown::Write).unwrap();
+
+    let mut buf = Vec::new();
+    remote.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"last");
+}
+
+#[test]
+fn chunking() {
+    let (mut local, mut remote) = FaultyChannel::new()
+        .chunk_size(3)
+        .bandwidth(600)
+        .build()
+        .unwrap();
+
+    local.write_all(b"0123456789").unwrap();
+
+    let mut received = Vec::new();
+    let mut buf = [0; 10];
+
+    while received.len() < 10 {
+        let n = remote.read(&mut buf).unwrap();
+        assert!(n <= 3, "read {} bytes in one chunk", n);
+        received.extend_from_slice(&buf[..n]);
+    }
+
+    assert_eq!(received, b"0123456789");
+}
+
+#[test]
+fn latency_jitter() {
+    let (mut local, mut remote) = FaultyChannel::new()
+        .latency(Duration::from_millis(50))
+        .jitter(Duration::from_millis(20))
+        .seed(7)
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    local.write_all(b"l4t3ncY!").unwrap();
+    read_assert!(remote, 8, b"l4t3ncY!");
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(50), "{:?}", elapsed);
+}
+
+#[test]
+fn latency_in_flight() {
+    let (mut local, mut remote) = FaultyChannel::new()
+        .latency(Duration::from_millis(50))
+        .chunk_size(1)
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    local.write_all(b"0123456789").unwrap();
+    read_assert!(remote, 10, b"0123456789");
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(50), "{:?}", elapsed);
+    // relaying the chunks one after another would take 10 times the latency
+    assert!(elapsed < Duration::from_millis(500), "{:?}", elapsed);
+}
+
+#[test]
+fn bandwidth() {
+    let (mut local, mut remote) = FaultyChannel::new()
+        .bandwidth(1000)
+        .chunk_size(50)
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+
+    let writer = thread::spawn(move || {
+        local.write_all(&[7; 200]).unwrap();
+        local
+    });
+
+    read_assert!(remote, 200, [7; 200]);
+    writer.join().unwrap();
+
+    // the last chunk's delay happens after it was forwarded
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(150), "{:?}", elapsed);
+}
+
+#[test]
+fn reset_after() {
+    let (mut local, mut remote) = FaultyChannel::new().reset_after(4).build().unwrap();
+
+    local.write_all(b"rst!ignored").unwrap();
+
+    let mut buf = Vec::new();
+    let error = remote.read_to_end(&mut buf).unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+    // data still in flight may be discarded by the reset
+    assert!(b"rst!".starts_with(&buf), "{:?}", buf);
+
+    let error = local.read(&mut [0; 1]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+}
+
+#[test]
+fn reset_forwarded() {
+    let (local, mut remote) = FaultyChannel::new().build().unwrap();
+
+    abort(local).unwrap();
+
+    let error = remote.read(&mut [0; 1]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+}
@@ -0,0 +1,70 @@
+use std::io::{ErrorKind, Write};
+use tcp_test::{assert_eof, assert_write_fails, channel, half_close, read_assert};
+
+#[test]
+fn request_response() {
+    let (mut client, mut server) = channel();
+
+    client.write_all(b"GET").unwrap();
+    half_close(&client).unwrap();
+
+    read_assert!(server, 3, b"GET");
+    assert_eof!(server);
+
+    server.write_all(b"200").unwrap();
+    half_close(&server).unwrap();
+
+    read_assert!(client, 3, b"200");
+    assert_eof!(client);
+}
+
+#[test]
+fn write_after_half_close() {
+    let (mut client, _server) = channel();
+
+    half_close(&client).unwrap();
+
+    assert_write_fails!(client, ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn write_after_peer_closed() {
+    let (mut client, server) = channel();
+
+    drop(server);
+
+    assert_write_fails!(client);
+}
+
+#[test]
+#[should_panic(expected = "received 4 bytes")]
+fn eof_with_data() {
+    let (mut client, mut server) = channel();
+
+    client.write_all(b"data").unwrap();
+    half_close(&client).unwrap();
+
+    assert_eof!(server);
+}
+
+#[test]
+#[should_panic(expected = "writing succeeded")]
+fn write_succeeds() {
+    let (mut client, _server) = channel();
+
+    assert_write_fails!(client);
+}
+
+#[test]
+fn write_fails_evaluates_once() {
+    let (mut client, server) = channel();
+    let mut evaluated = 0;
+
+    drop(server);
+
+    assert_write_fails!({
+        evaluated += 1;
+        &mut client
+    });
+    assert_eq!(evaluated, 1);
+}
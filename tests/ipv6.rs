@@ -0,0 +1,90 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tcp_test::{channel, channel_dual_stack, channel_on, channel_v6, read_assert};
+
+mod common;
+
+macro_rules! send_read {
+    ($local:expr, $remote:expr, $data:expr) => {
+        let (mut local, mut remote) = ($local, $remote);
+
+        local.write_all($data).unwrap();
+        read_assert!(remote, 8, $data);
+
+        remote.write_all($data).unwrap();
+        read_assert!(local, 8, $data);
+    };
+}
+
+#[test]
+fn v6() {
+    let (local, remote) = channel_v6();
+
+    assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+    assert_eq!(local.peer_addr().unwrap(), remote.local_addr().unwrap());
+    assert_eq!(local.peer_addr().unwrap().ip(), Ipv6Addr::LOCALHOST);
+
+    send_read!(local, remote, b"6Hn]x0#q");
+}
+
+#[test]
+fn v6_on() {
+    let address = common::unused_address("::1");
+    let (local, remote) = channel_on(address);
+
+    assert_eq!(local.peer_addr().unwrap(), address);
+    assert_eq!(remote.local_addr().unwrap(), address);
+
+    send_read!(local, remote, b"p;Wd8&Ua");
+}
+
+#[test]
+fn dual_stack() {
+    let (local, remote) = channel_dual_stack();
+    let mapped = IpAddr::V6(Ipv4Addr::LOCALHOST.to_ipv6_mapped());
+
+    assert_eq!(local.local_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+    assert_eq!(local.peer_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+    assert_eq!(remote.local_addr().unwrap().ip(), mapped);
+    assert_eq!(remote.peer_addr().unwrap().ip(), mapped);
+
+    assert_eq!(
+        local.local_addr().unwrap().port(),
+        remote.peer_addr().unwrap().port()
+    );
+
+    send_read!(local, remote, b"Dq7!sL0v");
+}
+
+#[test]
+fn dual_stack_unspecified() {
+    let (local, remote) = channel_on("[::]:0");
+
+    assert_eq!(local.peer_addr().unwrap().ip(), Ipv6Addr::LOCALHOST);
+    assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+
+    let port = remote.local_addr().unwrap().port();
+    let mut v4 = std::net::TcpStream::connect(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port))
+        .expect("the unspecified IPv6 listener accepts IPv4 connections");
+
+    // the background thread closes the stray IPv4 connection on the next call
+    drop(channel_on("[::]:0"));
+
+    let mut buf = Vec::new();
+    let _ = v4.read_to_end(&mut buf);
+    assert!(buf.is_empty());
+
+    send_read!(local, remote, b"&uY2k,9Q");
+}
+
+#[test]
+fn mixed() {
+    let (local_v4, remote_v4) = channel();
+    let (local_v6, remote_v6) = channel_v6();
+
+    assert!(local_v4.peer_addr().unwrap().is_ipv4());
+    assert!(local_v6.peer_addr().unwrap().is_ipv6());
+
+    send_read!(local_v4, remote_v4, b"4n>vZ1o+");
+    send_read!(local_v6, remote_v6, b"6nV+z1o>");
+}
@@ -1,11 +1,4 @@
-use lazy_static::lazy_static;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
-use tcp_test::{channel, read_assert};
-
-lazy_static! {
-    static ref DEFAULT_ADDRESS: SocketAddr =
-        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 31398));
-}
+use tcp_test::{channel, listener_addr, read_assert};
 
 macro_rules! send_read {
     ($data:expr) => {
@@ -13,8 +6,8 @@ macro_rules! send_read {
 
         let (mut local, mut remote) = channel();
 
-        assert_eq!(local.peer_addr().unwrap(), *DEFAULT_ADDRESS);
-        assert_eq!(remote.local_addr().unwrap(), *DEFAULT_ADDRESS);
+        assert_eq!(local.peer_addr().unwrap(), listener_addr());
+        assert_eq!(remote.local_addr().unwrap(), listener_addr());
 
         local.write_all($data).unwrap();
 
@@ -0,0 +1,53 @@
+#![cfg(feature = "smol")]
+
+use smol::io::{AsyncReadExt, AsyncWriteExt};
+use tcp_test::listener_addr;
+use tcp_test::smol::{channel, channel_on, try_channel};
+
+mod common;
+
+macro_rules! send_read {
+    ($local:expr, $remote:expr, $data:expr) => {
+        let (mut local, mut remote) = ($local, $remote);
+
+        local.write_all($data).await.unwrap();
+
+        let mut buf = [0; 8];
+        remote.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf, $data);
+    };
+}
+
+#[test]
+fn channel_0() {
+    smol::block_on(async {
+        let (local, remote) = channel().await;
+
+        assert_eq!(local.peer_addr().unwrap(), listener_addr());
+        assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+
+        send_read!(local, remote, b"sm0L!c_0");
+    });
+}
+
+#[test]
+fn channel_1() {
+    smol::block_on(async {
+        let (local, remote) = try_channel().await.unwrap();
+
+        send_read!(remote, local, b"0_c!L0ms");
+    });
+}
+
+#[test]
+fn channel_on_0() {
+    smol::block_on(async {
+        let address = common::unused_address("127.0.0.1");
+        let (local, remote) = channel_on(address).await;
+
+        assert_eq!(local.peer_addr().unwrap(), address);
+
+        send_read!(local, remote, b"sm;0N_31");
+    });
+}
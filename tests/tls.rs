@@ -0,0 +1,95 @@
+#![cfg(feature = "tls")]
+
+use std::io::Write;
+use tcp_test::tls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tcp_test::tls::rustls::RootCertStore;
+use tcp_test::tls::{ca_certificate, channel, TlsBuilder};
+use tcp_test::{listener_addr, read_assert, Error};
+
+macro_rules! send_read {
+    ($client:expr, $server:expr, $data:expr) => {
+        let (mut client, mut server) = ($client, $server);
+
+        client.write_all($data).unwrap();
+        client.flush().unwrap();
+        read_assert!(server, 8, $data);
+
+        server.write_all($data).unwrap();
+        server.flush().unwrap();
+        read_assert!(client, 8, $data);
+    };
+}
+
+#[test]
+fn channel_0() {
+    let (client, server) = channel();
+
+    assert_eq!(client.sock.peer_addr().unwrap(), listener_addr());
+    assert!(!client.conn.is_handshaking());
+    assert!(!server.conn.is_handshaking());
+    assert_eq!(server.conn.server_name(), Some("localhost"));
+
+    send_read!(client, server, b"t1s!C0_q");
+}
+
+#[test]
+fn untrusted_certificate() {
+    let (certificate, key) = self_signed();
+
+    let result = TlsBuilder::new()
+        .certificate(vec![certificate], PrivateKeyDer::Pkcs8(key.into()))
+        .server_name("tcp-test.example")
+        .build();
+
+    match result {
+        Err(Error::Tls(_)) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("the handshake succeeded with an untrusted certificate"),
+    }
+}
+
+#[test]
+fn alpn() {
+    let (client, server) = TlsBuilder::new()
+        .alpn(vec![b"tcp-test/1".to_vec(), b"tcp-test/0".to_vec()])
+        .build()
+        .unwrap();
+
+    assert_eq!(client.conn.alpn_protocol(), Some(&b"tcp-test/1"[..]));
+    assert_eq!(server.conn.alpn_protocol(), Some(&b"tcp-test/1"[..]));
+
+    send_read!(client, server, b"a1pN-t0$");
+}
+
+#[test]
+fn own_certificate() {
+    let (certificate, key) = self_signed();
+
+    let mut roots = RootCertStore::empty();
+    roots.add(certificate.clone()).unwrap();
+
+    let (client, server) = TlsBuilder::new()
+        .certificate(vec![certificate], PrivateKeyDer::Pkcs8(key.into()))
+        .root_certificates(roots)
+        .server_name("tcp-test.example")
+        .build()
+        .unwrap();
+
+    send_read!(client, server, b"0wN;c3Rt");
+}
+
+#[test]
+fn ca_certificate_stable() {
+    assert_eq!(ca_certificate(), ca_certificate());
+}
+
+/// Returns a self-signed certificate for `tcp-test.example` and its PKCS #8 key.
+fn self_signed() -> (CertificateDer<'static>, Vec<u8>) {
+    let key = rcgen::KeyPair::generate().unwrap();
+    let certificate = rcgen::CertificateParams::new(vec![String::from("tcp-test.example")])
+        .unwrap()
+        .self_signed(&key)
+        .unwrap();
+
+    (certificate.der().clone(), key.serialize_der())
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "tokio")]
+
+use tcp_test::listener_addr;
+use tcp_test::tokio::{channel, channel_on, try_channel};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+
+macro_rules! send_read {
+    ($local:expr, $remote:expr, $data:expr) => {
+        let (mut local, mut remote) = ($local, $remote);
+
+        local.write_all($data).await.unwrap();
+
+        let mut buf = [0; 8];
+        remote.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf, $data);
+    };
+}
+
+#[tokio::test]
+async fn channel_0() {
+    let (local, remote) = channel().await;
+
+    assert_eq!(local.peer_addr().unwrap(), listener_addr());
+    assert_eq!(local.local_addr().unwrap(), remote.peer_addr().unwrap());
+
+    send_read!(local, remote, b"t0k!o#Zq");
+}
+
+#[tokio::test]
+async fn channel_1() {
+    let (local, remote) = try_channel().await.unwrap();
+
+    send_read!(remote, local, b"Tq:1o0^k");
+}
+
+#[tokio::test]
+async fn channel_on_0() {
+    let address = common::unused_address("127.0.0.1");
+    let (local, remote) = channel_on(address).await;
+
+    assert_eq!(local.peer_addr().unwrap(), address);
+
+    send_read!(local, remote, b"o+N_31k4");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn concurrent() {
+    let (first, second) = tokio::join!(channel(), channel());
+
+    send_read!(first.0, first.1, b"c0nc-1Rr");
+    send_read!(second.0, second.1, b"c0nc-2Rr");
+}
@@ -0,0 +1,52 @@
+use std::io::Write;
+use std::net::{Shutdown, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use tcp_test::{read_assert, Transport};
+
+fn send_read<T: Transport>(data: &[u8; 8]) {
+    let (mut local, mut remote) = T::channel();
+
+    local.write_all(data).unwrap();
+    read_assert!(remote, 8, data);
+
+    remote.write_all(data).unwrap();
+    read_assert!(local, 8, data);
+}
+
+fn shutdown<T: Transport>() {
+    let (mut local, mut remote) = T::try_channel().unwrap();
+
+    local.write_all(b"last").unwrap();
+    local.shutdown(Shutdown::Write).unwrap();
+
+    let mut buf = Vec::new();
+    remote.read_to_end(&mut buf).unwrap();
+
+    assert_eq!(buf, b"last");
+}
+
+#[test]
+fn tcp() {
+    send_read::<TcpStream>(b"tcp!T5_0");
+    shutdown::<TcpStream>();
+}
+
+#[cfg(unix)]
+#[test]
+fn unix() {
+    send_read::<UnixStream>(b"un1x$Q;e");
+    shutdown::<UnixStream>();
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_on() {
+    let path = std::env::temp_dir().join(format!("tcp-test-{}.sock", std::process::id()));
+    let (mut local, mut remote) = tcp_test::unix::channel_on(&path);
+
+    assert!(!path.exists());
+
+    local.write_all(b"p4th#Ux0").unwrap();
+    read_assert!(remote, 8, b"p4th#Ux0");
+}